@@ -0,0 +1,178 @@
+#ifndef SYMMETRIC_PRE_H
+#define SYMMETRIC_PRE_H
+
+/* Generated by cbindgen from src/capi.rs, do not edit. */
+
+#include <stddef.h>
+#include <stdint.h>
+
+/**
+ * Length in bytes of a plaintext.
+ */
+#define SPRE_PLAINTEXT_LENGTH 32
+
+/**
+ * Length in bytes of a ciphertext.
+ */
+#define SPRE_CIPHERTEXT_LENGTH 36
+
+/**
+ * Length in bytes of a key fingerprint.
+ */
+#define SPRE_FINGERPRINT_LENGTH 8
+
+/**
+ * Error codes, mirroring the Rust `Error` enum.
+ */
+typedef enum {
+  SPRE_ERROR_OK = 0,
+  SPRE_ERROR_NULL_POINTER = 1,
+  SPRE_ERROR_UNKNOWN_EPOCH = 2,
+  SPRE_ERROR_EPOCH_MISMATCH = 3,
+  SPRE_ERROR_KEY_MISMATCH = 4,
+  SPRE_ERROR_UNKNOWN_RE_ENCRYPTION_KEY = 5,
+  SPRE_ERROR_UNKNOWN_RECIPIENT = 6,
+  SPRE_ERROR_INVALID_FORMAT = 7,
+  SPRE_ERROR_INVALID_ID = 8,
+  SPRE_ERROR_PASSPHRASE_REQUIRED = 9,
+  SPRE_ERROR_INTEGRITY_CHECK_FAILED = 10,
+  SPRE_ERROR_IO = 11,
+  SPRE_ERROR_AONT_HASH_MISMATCH = 12,
+} SpreError;
+
+/**
+ * Opaque handle to a `Key`.
+ */
+typedef struct SpreKey SpreKey;
+
+/**
+ * Opaque handle to a `ReEncryptionKey`.
+ */
+typedef struct SpreReEncryptionKey SpreReEncryptionKey;
+
+/**
+ * Returns a static, NUL-terminated description of `error`.
+ */
+const char *spre_error_message(SpreError error);
+
+/**
+ * Generates a new key into `*out`, to be freed with `spre_key_free`.
+ *
+ * # Safety
+ * `out` must be valid for writes.
+ */
+SpreError spre_key_generate(SpreKey **out);
+
+/**
+ * Frees a key returned by this library. `key` may be null.
+ *
+ * # Safety
+ * `key` must come from this library and must not be used afterwards.
+ */
+void spre_key_free(SpreKey *key);
+
+/**
+ * Writes the fingerprint of `key` into `out`.
+ *
+ * # Safety
+ * `key` must be a valid key and `out` valid for `SPRE_FINGERPRINT_LENGTH` bytes of writes.
+ */
+SpreError spre_key_fingerprint(const SpreKey *key, uint8_t *out);
+
+/**
+ * Serializes `key` into a buffer stored in `*out` and `*out_length`, to be freed with `spre_bytes_free`.
+ *
+ * # Safety
+ * `key` must be a valid key, `out` and `out_length` valid for writes.
+ */
+SpreError spre_key_to_bytes(const SpreKey *key,
+                            uint8_t **out,
+                            size_t *out_length);
+
+/**
+ * Deserializes a key from `bytes` into `*out`, to be freed with `spre_key_free`.
+ *
+ * # Safety
+ * `bytes` must be valid for `length` bytes of reads and `out` valid for writes.
+ */
+SpreError spre_key_from_bytes(const uint8_t *bytes, size_t length, SpreKey **out);
+
+/**
+ * Generates the re-encryption key from `old_key` to `new_key` into `*out`, to be freed with `spre_re_encryption_key_free`.
+ * Fails with `AontHashMismatch` if the two keys use different AONT hashes.
+ *
+ * # Safety
+ * `old_key` and `new_key` must be valid keys and `out` valid for writes.
+ */
+SpreError spre_re_encryption_key_generate(const SpreKey *old_key,
+                                          const SpreKey *new_key,
+                                          SpreReEncryptionKey **out);
+
+/**
+ * Frees a re-encryption key returned by this library. `re_encryption_key` may be null.
+ *
+ * # Safety
+ * `re_encryption_key` must come from this library and must not be used afterwards.
+ */
+void spre_re_encryption_key_free(SpreReEncryptionKey *re_encryption_key);
+
+/**
+ * Serializes `re_encryption_key` into a buffer stored in `*out` and `*out_length`, to be freed with `spre_bytes_free`.
+ *
+ * # Safety
+ * `re_encryption_key` must be a valid re-encryption key, `out` and `out_length` valid for writes.
+ */
+SpreError spre_re_encryption_key_to_bytes(const SpreReEncryptionKey *re_encryption_key,
+                                          uint8_t **out,
+                                          size_t *out_length);
+
+/**
+ * Deserializes a re-encryption key from `bytes` into `*out`, to be freed with `spre_re_encryption_key_free`.
+ *
+ * # Safety
+ * `bytes` must be valid for `length` bytes of reads and `out` valid for writes.
+ */
+SpreError spre_re_encryption_key_from_bytes(const uint8_t *bytes,
+                                            size_t length,
+                                            SpreReEncryptionKey **out);
+
+/**
+ * Frees a buffer returned by this library. `bytes` may be null.
+ *
+ * # Safety
+ * `bytes` and `length` must come from the same call to this library, and `bytes` must not be used afterwards.
+ */
+void spre_bytes_free(uint8_t *bytes,
+                     size_t length);
+
+/**
+ * Encrypts the `SPRE_PLAINTEXT_LENGTH` bytes of `plaintext` into the `SPRE_CIPHERTEXT_LENGTH` bytes of `out`.
+ *
+ * # Safety
+ * `key` must be a valid key, `plaintext` and `out` valid for reads and writes of their lengths.
+ */
+SpreError spre_encrypt(const SpreKey *key,
+                       const uint8_t *plaintext,
+                       uint8_t *out);
+
+/**
+ * Decrypts the `SPRE_CIPHERTEXT_LENGTH` bytes of `ciphertext` into the `SPRE_PLAINTEXT_LENGTH` bytes of `out`.
+ *
+ * # Safety
+ * `key` must be a valid key, `ciphertext` and `out` valid for reads and writes of their lengths.
+ */
+SpreError spre_decrypt(const SpreKey *key,
+                       const uint8_t *ciphertext,
+                       uint8_t *out);
+
+/**
+ * Re-encrypts the `SPRE_CIPHERTEXT_LENGTH` bytes of `ciphertext` into the `SPRE_CIPHERTEXT_LENGTH` bytes of `out`.
+ *
+ * # Safety
+ * `re_encryption_key` must be a valid re-encryption key, `ciphertext` and `out` valid for reads and writes of their lengths.
+ */
+SpreError spre_re_encrypt(const SpreReEncryptionKey *re_encryption_key,
+                          const uint8_t *ciphertext,
+                          uint8_t *out);
+
+#endif  /* SYMMETRIC_PRE_H */
@@ -0,0 +1,58 @@
+use crate::{Key, ReEncryptionKey};
+
+#[derive(Debug, Clone)]
+pub struct CollusionReport {
+    pub recovered_key: Key,
+    pub success: bool,
+}
+
+pub fn recover_new_key(re_encryption_key: &ReEncryptionKey, old_key: &Key) -> CollusionReport {
+    let new_p2 = re_encryption_key.new_p2();
+    let recovered_key = Key::from_permutations(
+        old_key.p1().compose(&re_encryption_key.cp1()),
+        new_p2,
+        old_key.p3().compose(&re_encryption_key.cp3()),
+        re_encryption_key.new_kx(),
+    )
+    .with_aont_hash(old_key.aont_hash());
+    let success = ReEncryptionKey::generate(old_key, &recovered_key).as_ref() == Ok(re_encryption_key);
+    CollusionReport { recovered_key, success }
+}
+
+pub fn recover_old_key(re_encryption_key: &ReEncryptionKey, new_key: &Key) -> CollusionReport {
+    recover_new_key(&re_encryption_key.inverse(), new_key)
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::{AontHash, Key, ReEncryptionKey};
+
+    #[test]
+    fn test_delegatee_collusion() {
+        let old_key = Key::generate();
+        let new_key = Key::generate();
+        let re_encryption_key = ReEncryptionKey::generate(&old_key, &new_key).unwrap();
+        let report = super::recover_old_key(&re_encryption_key, &new_key);
+        assert!(report.success);
+        assert_eq!(report.recovered_key, old_key);
+    }
+
+    #[test]
+    fn test_delegator_collusion() {
+        let old_key = Key::generate().with_aont_hash(AontHash::Blake3);
+        let new_key = Key::generate().with_aont_hash(AontHash::Blake3);
+        let re_encryption_key = ReEncryptionKey::generate(&old_key, &new_key).unwrap();
+        let report = super::recover_new_key(&re_encryption_key, &old_key);
+        assert!(report.success);
+        assert_eq!(report.recovered_key, new_key);
+    }
+
+    #[test]
+    fn test_unrelated_key() {
+        let old_key = Key::generate();
+        let new_key = Key::generate();
+        let re_encryption_key = ReEncryptionKey::generate(&old_key, &new_key).unwrap();
+        let report = super::recover_old_key(&re_encryption_key, &Key::generate());
+        assert!(!report.success);
+    }
+}
@@ -1,9 +1,101 @@
 use collar::CollectArray;
-use rand::Rng;
-use sha3::{Digest, Sha3_256};
+use digest::{Digest, ExtendableOutput, FixedOutput, HashMarker, Output, OutputSizeUser, Update};
+use rand::{CryptoRng, Rng};
+use sha2::Sha256;
+use sha3::{Sha3_256, Shake128};
+use crate::error::Error;
 use crate::{BLOCK_SIZE_BYTE, MESSAGE_BLOCKS_COUNT};
 use crate::utils::xor_array;
 
+const FEISTEL_DOMAIN: &[u8] = b"symmetric_pre feistel";
+const OAEP_DOMAIN: &[u8] = b"symmetric_pre oaep";
+const FEISTEL_ROUNDS: u8 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AontKind {
+    // Every message block XORed with the same random key, followed by the key XORed with the hash of those blocks.
+    #[default]
+    HashXor,
+    // Rivest's package transform: the message encrypted with a random key by a block cipher in CTR mode, followed by
+    // the key XORed with the hash of the encrypted blocks.
+    PackageTransform,
+    // Boyko's OAEP: the message XORed with a mask expanded from a random seed, followed by the seed XORed with the
+    // hash of the masked message.
+    Oaep,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AontHash {
+    #[default]
+    Sha3_256,
+    Sha256,
+    Blake3,
+    Shake128,
+}
+
+impl AontHash {
+    pub(crate) fn to_byte(self) -> u8 {
+        match self {
+            AontHash::Sha3_256 => 0,
+            AontHash::Sha256 => 1,
+            AontHash::Blake3 => 2,
+            AontHash::Shake128 => 3,
+        }
+    }
+
+    pub(crate) fn from_byte(byte: u8) -> Result<Self, Error> {
+        match byte {
+            0 => Ok(AontHash::Sha3_256),
+            1 => Ok(AontHash::Sha256),
+            2 => Ok(AontHash::Blake3),
+            3 => Ok(AontHash::Shake128),
+            _ => Err(Error::InvalidFormat("unknown AONT hash")),
+        }
+    }
+}
+
+// SHAKE128 squeezed to exactly one block, so that it can be used wherever a `Digest` is.
+#[derive(Clone, Default)]
+pub(crate) struct Shake128Block(Shake128);
+
+impl HashMarker for Shake128Block {}
+
+impl OutputSizeUser for Shake128Block {
+    type OutputSize = typenum::U<BLOCK_SIZE_BYTE>;
+}
+
+impl Update for Shake128Block {
+    fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+}
+
+impl FixedOutput for Shake128Block {
+    fn finalize_into(self, out: &mut Output<Self>) {
+        self.0.finalize_xof_into(out);
+    }
+}
+
+impl AontKind {
+    pub(crate) fn to_byte(self) -> u8 {
+        match self {
+            AontKind::HashXor => 0,
+            AontKind::PackageTransform => 1,
+            AontKind::Oaep => 2,
+        }
+    }
+
+    pub(crate) fn from_byte(byte: u8) -> Result<Self, Error> {
+        match byte {
+            0 => Ok(AontKind::HashXor),
+            1 => Ok(AontKind::PackageTransform),
+            2 => Ok(AontKind::Oaep),
+            _ => Err(Error::InvalidFormat("unknown AONT kind")),
+        }
+    }
+}
+
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Clone)]
 pub(crate) struct AONT  {
     pub(crate) encrypted: [u8; (MESSAGE_BLOCKS_COUNT + 1) * BLOCK_SIZE_BYTE]
@@ -16,37 +108,161 @@ impl AONT {
         }
     }
 
-    pub(crate) fn from_message(input: &[u8; MESSAGE_BLOCKS_COUNT * BLOCK_SIZE_BYTE]) -> Self {
-        let mut rng = rand::thread_rng();
-        let mut hasher = Sha3_256::new();
-        let mut random_key_xor = [0u8; BLOCK_SIZE_BYTE];
-        rng.fill(&mut random_key_xor);
-        let xored: [u8; MESSAGE_BLOCKS_COUNT * BLOCK_SIZE_BYTE] = input.chunks_exact(BLOCK_SIZE_BYTE).map(|chunk| xor_array::<4>(chunk.try_into().unwrap(), &random_key_xor)).flatten().collect_array();
-        Digest::update(&mut hasher, &xored);
-        let hash: [u8; BLOCK_SIZE_BYTE] = (&hasher.finalize()[..BLOCK_SIZE_BYTE]).try_into().unwrap();
-        let hash_xor_key = xor_array(&hash, &random_key_xor);
+    pub(crate) fn from_message<R: CryptoRng + Rng + ?Sized>(kind: AontKind, hash: AontHash, input: &[u8; MESSAGE_BLOCKS_COUNT * BLOCK_SIZE_BYTE], rng: &mut R) -> Self {
+        match hash {
+            AontHash::Sha3_256 => Self::from_message_with::<Sha3_256, R>(kind, input, rng),
+            AontHash::Sha256 => Self::from_message_with::<Sha256, R>(kind, input, rng),
+            AontHash::Blake3 => Self::from_message_with::<blake3::Hasher, R>(kind, input, rng),
+            AontHash::Shake128 => Self::from_message_with::<Shake128Block, R>(kind, input, rng),
+        }
+    }
+
+    pub(crate) fn retrieve_message(&self, kind: AontKind, hash: AontHash) -> [u8; MESSAGE_BLOCKS_COUNT * BLOCK_SIZE_BYTE] {
+        match hash {
+            AontHash::Sha3_256 => self.retrieve_message_with::<Sha3_256>(kind),
+            AontHash::Sha256 => self.retrieve_message_with::<Sha256>(kind),
+            AontHash::Blake3 => self.retrieve_message_with::<blake3::Hasher>(kind),
+            AontHash::Shake128 => self.retrieve_message_with::<Shake128Block>(kind),
+        }
+    }
+
+    fn from_message_with<D: Digest, R: CryptoRng + Rng + ?Sized>(kind: AontKind, input: &[u8; MESSAGE_BLOCKS_COUNT * BLOCK_SIZE_BYTE], rng: &mut R) -> Self {
+        let mut random_key = [0u8; BLOCK_SIZE_BYTE];
+        rng.fill(&mut random_key);
+        let masked: [u8; MESSAGE_BLOCKS_COUNT * BLOCK_SIZE_BYTE] = match kind {
+            AontKind::HashXor => input.chunks_exact(BLOCK_SIZE_BYTE).flat_map(|chunk| xor_array::<BLOCK_SIZE_BYTE>(chunk.try_into().unwrap(), &random_key)).collect_array(),
+            AontKind::PackageTransform => xor_array(input, &ctr_keystream::<D>(&random_key)),
+            AontKind::Oaep => xor_array(input, &hash_parts::<D, { MESSAGE_BLOCKS_COUNT * BLOCK_SIZE_BYTE }>(&[OAEP_DOMAIN, &random_key])),
+        };
+        let hash_xor_key = xor_array(&hash_parts::<D, BLOCK_SIZE_BYTE>(&[&masked]), &random_key);
         Self {
-            encrypted: xored.iter().chain(hash_xor_key.iter()).cloned().collect_array()
+            encrypted: masked.iter().chain(hash_xor_key.iter()).cloned().collect_array()
         }
     }
 
-    pub(crate) fn retrieve_message(&self) -> [u8; MESSAGE_BLOCKS_COUNT * BLOCK_SIZE_BYTE] {
-        let mut hasher = Sha3_256::new();
-        let (message, hash_xor_key) = self.encrypted.split_at(MESSAGE_BLOCKS_COUNT * BLOCK_SIZE_BYTE);
-        Digest::update(&mut hasher, message);
-        let hash: [u8; BLOCK_SIZE_BYTE] = (&hasher.finalize()[..BLOCK_SIZE_BYTE]).try_into().unwrap();
-        let random_key_xor = xor_array(&hash, &hash_xor_key.try_into().unwrap());
-        message.chunks_exact(BLOCK_SIZE_BYTE).map(|chunk| xor_array::<4>(chunk.try_into().unwrap(), &random_key_xor)).flatten().collect_array()
+    fn retrieve_message_with<D: Digest>(&self, kind: AontKind) -> [u8; MESSAGE_BLOCKS_COUNT * BLOCK_SIZE_BYTE] {
+        let (masked, hash_xor_key) = self.encrypted.split_at(MESSAGE_BLOCKS_COUNT * BLOCK_SIZE_BYTE);
+        let masked: &[u8; MESSAGE_BLOCKS_COUNT * BLOCK_SIZE_BYTE] = masked.try_into().unwrap();
+        let random_key = xor_array(&hash_parts::<D, BLOCK_SIZE_BYTE>(&[masked]), &hash_xor_key.try_into().unwrap());
+        match kind {
+            AontKind::HashXor => masked.chunks_exact(BLOCK_SIZE_BYTE).flat_map(|chunk| xor_array::<BLOCK_SIZE_BYTE>(chunk.try_into().unwrap(), &random_key)).collect_array(),
+            AontKind::PackageTransform => xor_array(masked, &ctr_keystream::<D>(&random_key)),
+            AontKind::Oaep => xor_array(masked, &hash_parts::<D, { MESSAGE_BLOCKS_COUNT * BLOCK_SIZE_BYTE }>(&[OAEP_DOMAIN, &random_key])),
+        }
     }
 }
 
-#[cfg(test)]
+// Blocks no larger than the digest are a truncated hash of the input; larger ones concatenate the hashes of the input
+// followed by a 4-byte counter, so that keys and final blocks can outgrow the digest.
+fn hash_parts<D: Digest, const OUTPUT_SIZE: usize>(parts: &[&[u8]]) -> [u8; OUTPUT_SIZE] {
+    let digest_size = <D as Digest>::output_size();
+    let mut output = [0u8; OUTPUT_SIZE];
+    for (counter, chunk) in output.chunks_mut(digest_size).enumerate() {
+        let mut hasher = D::new();
+        for part in parts {
+            Digest::update(&mut hasher, part);
+        }
+        if OUTPUT_SIZE > digest_size {
+            Digest::update(&mut hasher, (counter as u32).to_be_bytes());
+        }
+        chunk.copy_from_slice(&hasher.finalize()[..chunk.len()]);
+    }
+    output
+}
+
+fn ctr_keystream<D: Digest>(key: &[u8; BLOCK_SIZE_BYTE]) -> [u8; MESSAGE_BLOCKS_COUNT * BLOCK_SIZE_BYTE] {
+    (0..MESSAGE_BLOCKS_COUNT)
+        .flat_map(|i| {
+            let mut counter = [0u8; BLOCK_SIZE_BYTE];
+            counter[BLOCK_SIZE_BYTE - 4..].copy_from_slice(&(i as u32).to_be_bytes());
+            feistel_encrypt::<D>(key, &counter)
+        })
+        .collect_array()
+}
+
+// The scheme's blocks are too small for a standard block cipher, so the package transform uses a balanced Feistel
+// network with the AONT hash as its round function, which four rounds make a pseudorandom permutation (Luby–Rackoff).
+fn feistel_encrypt<D: Digest>(key: &[u8; BLOCK_SIZE_BYTE], block: &[u8; BLOCK_SIZE_BYTE]) -> [u8; BLOCK_SIZE_BYTE] {
+    let (left, right) = block.split_at(BLOCK_SIZE_BYTE / 2);
+    let mut left: [u8; BLOCK_SIZE_BYTE / 2] = left.try_into().unwrap();
+    let mut right: [u8; BLOCK_SIZE_BYTE / 2] = right.try_into().unwrap();
+    for round in 0..FEISTEL_ROUNDS {
+        let round_output = hash_parts::<D, { BLOCK_SIZE_BYTE / 2 }>(&[FEISTEL_DOMAIN, key, &[round], &right]);
+        (left, right) = (right, xor_array(&left, &round_output));
+    }
+    left.iter().chain(right.iter()).copied().collect_array()
+}
+
+#[cfg(all(test, feature = "std"))]
 mod tests {
+    use super::{hash_parts, AontHash, AontKind, Shake128Block};
+    use crate::BLOCK_SIZE_BYTE;
+    use digest::{Digest, ExtendableOutput, Update};
+    use sha2::Sha256;
+    use sha3::{Sha3_256, Shake128};
+
+    const KINDS: [AontKind; 3] = [AontKind::HashXor, AontKind::PackageTransform, AontKind::Oaep];
+    const HASHES: [AontHash; 4] = [AontHash::Sha3_256, AontHash::Sha256, AontHash::Blake3, AontHash::Shake128];
+
     #[test]
     fn test_aont() {
-        let message = [0u8; 32];
-        let aont = super::AONT::from_message(&message);
-        let retrieved_message = aont.retrieve_message();
-        assert_eq!(message, retrieved_message);
+        for kind in KINDS {
+            for hash in HASHES {
+                let message = [0u8; 32];
+                let aont = super::AONT::from_message(kind, hash, &message, &mut rand::thread_rng());
+                let retrieved_message = aont.retrieve_message(kind, hash);
+                assert_eq!(message, retrieved_message);
+            }
+        }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_aont_needs_every_block() {
+        for kind in KINDS {
+            for hash in HASHES {
+                let message = *b"les sanglots longs des violons !";
+                let mut aont = super::AONT::from_message(kind, hash, &message, &mut rand::thread_rng());
+                aont.encrypted[0] ^= 1;
+                assert_ne!(aont.retrieve_message(kind, hash)[4..], message[4..]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_aont_needs_the_same_hash() {
+        let message = *b"les sanglots longs des violons !";
+        let aont = super::AONT::from_message(AontKind::HashXor, AontHash::Sha256, &message, &mut rand::thread_rng());
+        assert_ne!(aont.retrieve_message(AontKind::HashXor, AontHash::Sha3_256), message);
+    }
+
+    #[test]
+    fn test_aont_kind_bytes() {
+        for kind in KINDS {
+            assert_eq!(AontKind::from_byte(kind.to_byte()), Ok(kind));
+        }
+        assert!(AontKind::from_byte(3).is_err());
+        for hash in HASHES {
+            assert_eq!(AontHash::from_byte(hash.to_byte()), Ok(hash));
+        }
+        assert!(AontHash::from_byte(4).is_err());
+    }
+
+    #[test]
+    fn test_hash_parts() {
+        // Up to the digest size, hashing is plain truncation, which keeps SHA3-256 AONTs compatible with older envelopes.
+        assert_eq!(hash_parts::<Sha3_256, 4>(&[b"ab", b"c"]), Sha3_256::digest(b"abc")[..4]);
+
+        // Beyond it, each digest-sized chunk hashes a distinct counter.
+        let long = hash_parts::<Sha256, 80>(&[b"abc"]);
+        assert_eq!(long[..32], Sha256::digest(b"abc\0\0\0\0")[..]);
+        assert_eq!(long[32..64], Sha256::digest(b"abc\0\0\0\x01")[..]);
+        assert_eq!(long[64..], Sha256::digest(b"abc\0\0\0\x02")[..16]);
+
+        let mut shake = Shake128::default();
+        shake.update(b"abc");
+        let mut expected = [0u8; BLOCK_SIZE_BYTE];
+        shake.finalize_xof_into(&mut expected);
+        assert_eq!(hash_parts::<Shake128Block, BLOCK_SIZE_BYTE>(&[b"abc"]), expected);
+        assert_eq!(hash_parts::<Shake128Block, 64>(&[b"abc"]).len(), 64);
+    }
+}
@@ -0,0 +1,92 @@
+use std::net::TcpListener;
+#[cfg(unix)]
+use std::os::unix::net::UnixListener;
+use std::process::exit;
+use std::sync::Arc;
+use symmetric_pre::proxy::Proxy;
+use symmetric_pre::FileKeyStore;
+
+const USAGE: &str = "usage: symmetric-pre-proxy --rekeys <dir> (--tcp <address> | --unix <path> | --http <address>)";
+const PASSPHRASE_VARIABLE: &str = "SYMMETRIC_PRE_PASSPHRASE";
+
+fn main() {
+    let mut rekeys = None;
+    let mut tcp = None;
+    let mut unix = None;
+    let mut http = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let value = args.next();
+        match (arg.as_str(), value) {
+            ("--rekeys", Some(value)) => rekeys = Some(value),
+            ("--tcp", Some(value)) => tcp = Some(value),
+            ("--unix", Some(value)) => unix = Some(value),
+            ("--http", Some(value)) => http = Some(value),
+            _ => usage(),
+        }
+    }
+    let Some(rekeys) = rekeys else {
+        usage();
+    };
+    let store = open_store(&rekeys);
+
+    let result = match (tcp, unix, http) {
+        (Some(address), None, None) => TcpListener::bind(address).and_then(|listener| {
+            let proxy = load_proxy(&store);
+            println!("listening on {}", listener.local_addr()?);
+            proxy.serve_tcp(listener)
+        }),
+        #[cfg(unix)]
+        (None, Some(path), None) => UnixListener::bind(&path).and_then(|listener| {
+            let proxy = load_proxy(&store);
+            println!("listening on {}", path);
+            proxy.serve_unix(listener)
+        }),
+        #[cfg(feature = "http")]
+        (None, None, Some(address)) => TcpListener::bind(address).and_then(|listener| {
+            let local_address = listener.local_addr()?;
+            let proxy = symmetric_pre::http::HttpProxy::new(listener)?;
+            proxy.load_store(&store).map_err(std::io::Error::other)?;
+            println!("listening on {}", local_address);
+            proxy.serve()
+        }),
+        #[cfg(not(feature = "http"))]
+        (None, None, Some(_)) => {
+            eprintln!("symmetric-pre-proxy was built without the http feature");
+            exit(2);
+        }
+        _ => usage(),
+    };
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        exit(1);
+    }
+}
+
+fn open_store(rekeys: &str) -> FileKeyStore {
+    match FileKeyStore::open(rekeys) {
+        Ok(store) => match std::env::var(PASSPHRASE_VARIABLE) {
+            Ok(passphrase) => store.with_passphrase(&passphrase),
+            Err(_) => store,
+        },
+        Err(e) => {
+            eprintln!("cannot open key store {}: {}", rekeys, e);
+            exit(1);
+        }
+    }
+}
+
+fn load_proxy(store: &FileKeyStore) -> Arc<Proxy> {
+    match Proxy::from_store(store) {
+        Ok(proxy) => Arc::new(proxy),
+        Err(e) => {
+            eprintln!("cannot load re-encryption keys: {}", e);
+            exit(1);
+        }
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    exit(2);
+}
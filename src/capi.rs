@@ -0,0 +1,270 @@
+use crate::error::Error;
+use crate::{decrypt, encrypt, re_encrypt, Encrypted, Fingerprint, Key, Plaintext, ReEncryptionKey};
+use std::ffi::{c_char, CStr};
+use std::ptr;
+use std::slice;
+
+/// Length in bytes of a plaintext.
+pub const SPRE_PLAINTEXT_LENGTH: usize = 32;
+/// Length in bytes of a ciphertext.
+pub const SPRE_CIPHERTEXT_LENGTH: usize = 36;
+/// Length in bytes of a key fingerprint.
+pub const SPRE_FINGERPRINT_LENGTH: usize = 8;
+
+const _: () = assert!(SPRE_PLAINTEXT_LENGTH == size_of::<Plaintext>());
+const _: () = assert!(SPRE_CIPHERTEXT_LENGTH == size_of::<Encrypted>());
+const _: () = assert!(SPRE_FINGERPRINT_LENGTH == size_of::<Fingerprint>());
+
+/// Opaque handle to a `Key`.
+pub struct SpreKey(Key);
+
+/// Opaque handle to a `ReEncryptionKey`.
+pub struct SpreReEncryptionKey(ReEncryptionKey);
+
+/// Error codes, mirroring the Rust `Error` enum.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpreError {
+    Ok = 0,
+    NullPointer = 1,
+    UnknownEpoch = 2,
+    EpochMismatch = 3,
+    KeyMismatch = 4,
+    UnknownReEncryptionKey = 5,
+    UnknownRecipient = 6,
+    InvalidFormat = 7,
+    InvalidId = 8,
+    PassphraseRequired = 9,
+    IntegrityCheckFailed = 10,
+    Io = 11,
+    AontHashMismatch = 12,
+}
+
+impl From<Error> for SpreError {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::UnknownEpoch(_) => SpreError::UnknownEpoch,
+            Error::EpochMismatch { .. } => SpreError::EpochMismatch,
+            Error::KeyMismatch { .. } => SpreError::KeyMismatch,
+            Error::AontHashMismatch { .. } => SpreError::AontHashMismatch,
+            Error::UnknownReEncryptionKey { .. } => SpreError::UnknownReEncryptionKey,
+            Error::UnknownRecipient(_) => SpreError::UnknownRecipient,
+            Error::InvalidFormat(_) => SpreError::InvalidFormat,
+            Error::InvalidId(_) => SpreError::InvalidId,
+            Error::PassphraseRequired => SpreError::PassphraseRequired,
+            Error::IntegrityCheckFailed => SpreError::IntegrityCheckFailed,
+            Error::Io(_) => SpreError::Io,
+        }
+    }
+}
+
+/// Returns a static, NUL-terminated description of `error`.
+#[no_mangle]
+pub extern "C" fn spre_error_message(error: SpreError) -> *const c_char {
+    let message: &'static CStr = match error {
+        SpreError::Ok => c"success",
+        SpreError::NullPointer => c"null pointer",
+        SpreError::UnknownEpoch => c"unknown epoch",
+        SpreError::EpochMismatch => c"epoch mismatch",
+        SpreError::KeyMismatch => c"key mismatch",
+        SpreError::UnknownReEncryptionKey => c"unknown re-encryption key",
+        SpreError::UnknownRecipient => c"unknown recipient",
+        SpreError::InvalidFormat => c"invalid format",
+        SpreError::InvalidId => c"invalid id",
+        SpreError::PassphraseRequired => c"a passphrase is required",
+        SpreError::IntegrityCheckFailed => c"integrity check failed",
+        SpreError::Io => c"I/O error",
+        SpreError::AontHashMismatch => c"AONT hash mismatch",
+    };
+    message.as_ptr()
+}
+
+/// Generates a new key into `*out`, to be freed with `spre_key_free`.
+///
+/// # Safety
+/// `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn spre_key_generate(out: *mut *mut SpreKey) -> SpreError {
+    if out.is_null() {
+        return SpreError::NullPointer;
+    }
+    *out = Box::into_raw(Box::new(SpreKey(Key::generate())));
+    SpreError::Ok
+}
+
+/// Frees a key returned by this library. `key` may be null.
+///
+/// # Safety
+/// `key` must come from this library and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn spre_key_free(key: *mut SpreKey) {
+    if !key.is_null() {
+        drop(Box::from_raw(key));
+    }
+}
+
+/// Writes the fingerprint of `key` into `out`.
+///
+/// # Safety
+/// `key` must be a valid key and `out` valid for `SPRE_FINGERPRINT_LENGTH` bytes of writes.
+#[no_mangle]
+pub unsafe extern "C" fn spre_key_fingerprint(key: *const SpreKey, out: *mut u8) -> SpreError {
+    if key.is_null() || out.is_null() {
+        return SpreError::NullPointer;
+    }
+    ptr::copy_nonoverlapping((*key).0.fingerprint().as_ptr(), out, SPRE_FINGERPRINT_LENGTH);
+    SpreError::Ok
+}
+
+/// Serializes `key` into a buffer stored in `*out` and `*out_length`, to be freed with `spre_bytes_free`.
+///
+/// # Safety
+/// `key` must be a valid key, `out` and `out_length` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn spre_key_to_bytes(key: *const SpreKey, out: *mut *mut u8, out_length: *mut usize) -> SpreError {
+    if key.is_null() {
+        return SpreError::NullPointer;
+    }
+    export_bytes((*key).0.to_bytes(), out, out_length)
+}
+
+/// Deserializes a key from `bytes` into `*out`, to be freed with `spre_key_free`.
+///
+/// # Safety
+/// `bytes` must be valid for `length` bytes of reads and `out` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn spre_key_from_bytes(bytes: *const u8, length: usize, out: *mut *mut SpreKey) -> SpreError {
+    if bytes.is_null() || out.is_null() {
+        return SpreError::NullPointer;
+    }
+    match Key::from_bytes(slice::from_raw_parts(bytes, length)) {
+        Ok(key) => {
+            *out = Box::into_raw(Box::new(SpreKey(key)));
+            SpreError::Ok
+        }
+        Err(e) => e.into(),
+    }
+}
+
+/// Generates the re-encryption key from `old_key` to `new_key` into `*out`, to be freed with `spre_re_encryption_key_free`.
+/// Fails with `AontHashMismatch` if the two keys use different AONT hashes.
+///
+/// # Safety
+/// `old_key` and `new_key` must be valid keys and `out` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn spre_re_encryption_key_generate(old_key: *const SpreKey, new_key: *const SpreKey, out: *mut *mut SpreReEncryptionKey) -> SpreError {
+    if old_key.is_null() || new_key.is_null() || out.is_null() {
+        return SpreError::NullPointer;
+    }
+    match ReEncryptionKey::generate(&(*old_key).0, &(*new_key).0) {
+        Ok(re_encryption_key) => {
+            *out = Box::into_raw(Box::new(SpreReEncryptionKey(re_encryption_key)));
+            SpreError::Ok
+        }
+        Err(e) => e.into(),
+    }
+}
+
+/// Frees a re-encryption key returned by this library. `re_encryption_key` may be null.
+///
+/// # Safety
+/// `re_encryption_key` must come from this library and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn spre_re_encryption_key_free(re_encryption_key: *mut SpreReEncryptionKey) {
+    if !re_encryption_key.is_null() {
+        drop(Box::from_raw(re_encryption_key));
+    }
+}
+
+/// Serializes `re_encryption_key` into a buffer stored in `*out` and `*out_length`, to be freed with `spre_bytes_free`.
+///
+/// # Safety
+/// `re_encryption_key` must be a valid re-encryption key, `out` and `out_length` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn spre_re_encryption_key_to_bytes(re_encryption_key: *const SpreReEncryptionKey, out: *mut *mut u8, out_length: *mut usize) -> SpreError {
+    if re_encryption_key.is_null() {
+        return SpreError::NullPointer;
+    }
+    export_bytes((*re_encryption_key).0.to_bytes(), out, out_length)
+}
+
+/// Deserializes a re-encryption key from `bytes` into `*out`, to be freed with `spre_re_encryption_key_free`.
+///
+/// # Safety
+/// `bytes` must be valid for `length` bytes of reads and `out` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn spre_re_encryption_key_from_bytes(bytes: *const u8, length: usize, out: *mut *mut SpreReEncryptionKey) -> SpreError {
+    if bytes.is_null() || out.is_null() {
+        return SpreError::NullPointer;
+    }
+    match ReEncryptionKey::from_bytes(slice::from_raw_parts(bytes, length)) {
+        Ok(re_encryption_key) => {
+            *out = Box::into_raw(Box::new(SpreReEncryptionKey(re_encryption_key)));
+            SpreError::Ok
+        }
+        Err(e) => e.into(),
+    }
+}
+
+/// Frees a buffer returned by this library. `bytes` may be null.
+///
+/// # Safety
+/// `bytes` and `length` must come from the same call to this library, and `bytes` must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn spre_bytes_free(bytes: *mut u8, length: usize) {
+    if !bytes.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(bytes, length)));
+    }
+}
+
+/// Encrypts the `SPRE_PLAINTEXT_LENGTH` bytes of `plaintext` into the `SPRE_CIPHERTEXT_LENGTH` bytes of `out`.
+///
+/// # Safety
+/// `key` must be a valid key, `plaintext` and `out` valid for reads and writes of their lengths.
+#[no_mangle]
+pub unsafe extern "C" fn spre_encrypt(key: *const SpreKey, plaintext: *const u8, out: *mut u8) -> SpreError {
+    if key.is_null() || plaintext.is_null() || out.is_null() {
+        return SpreError::NullPointer;
+    }
+    let encrypted = encrypt(&*(plaintext as *const Plaintext), &(*key).0);
+    ptr::copy_nonoverlapping(encrypted.as_ptr(), out, SPRE_CIPHERTEXT_LENGTH);
+    SpreError::Ok
+}
+
+/// Decrypts the `SPRE_CIPHERTEXT_LENGTH` bytes of `ciphertext` into the `SPRE_PLAINTEXT_LENGTH` bytes of `out`.
+///
+/// # Safety
+/// `key` must be a valid key, `ciphertext` and `out` valid for reads and writes of their lengths.
+#[no_mangle]
+pub unsafe extern "C" fn spre_decrypt(key: *const SpreKey, ciphertext: *const u8, out: *mut u8) -> SpreError {
+    if key.is_null() || ciphertext.is_null() || out.is_null() {
+        return SpreError::NullPointer;
+    }
+    let decrypted = decrypt(&*(ciphertext as *const Encrypted), &(*key).0);
+    ptr::copy_nonoverlapping(decrypted.as_ptr(), out, SPRE_PLAINTEXT_LENGTH);
+    SpreError::Ok
+}
+
+/// Re-encrypts the `SPRE_CIPHERTEXT_LENGTH` bytes of `ciphertext` into the `SPRE_CIPHERTEXT_LENGTH` bytes of `out`.
+///
+/// # Safety
+/// `re_encryption_key` must be a valid re-encryption key, `ciphertext` and `out` valid for reads and writes of their lengths.
+#[no_mangle]
+pub unsafe extern "C" fn spre_re_encrypt(re_encryption_key: *const SpreReEncryptionKey, ciphertext: *const u8, out: *mut u8) -> SpreError {
+    if re_encryption_key.is_null() || ciphertext.is_null() || out.is_null() {
+        return SpreError::NullPointer;
+    }
+    let re_encrypted = re_encrypt(&*(ciphertext as *const Encrypted), &(*re_encryption_key).0);
+    ptr::copy_nonoverlapping(re_encrypted.as_ptr(), out, SPRE_CIPHERTEXT_LENGTH);
+    SpreError::Ok
+}
+
+unsafe fn export_bytes(bytes: Vec<u8>, out: *mut *mut u8, out_length: *mut usize) -> SpreError {
+    if out.is_null() || out_length.is_null() {
+        return SpreError::NullPointer;
+    }
+    let bytes = bytes.into_boxed_slice();
+    *out_length = bytes.len();
+    *out = Box::into_raw(bytes) as *mut u8;
+    SpreError::Ok
+}
@@ -1,46 +1,140 @@
-use crate::aont::AONT;
+use crate::aont::{AontHash, AontKind, AONT};
+use crate::envelope::MAX_RECIPIENTS;
+use crate::error::Error;
 use crate::keygen::Key;
-use crate::permutations::{depermute_block, depermute_block_set, permute_block, permute_block_set};
 use crate::utils::xor_array;
-use crate::{Encrypted, Plaintext, ReEncryptionKey, BLOCK_SIZE_BITS, BLOCK_SIZE_BYTE, MESSAGE_BLOCKS_COUNT};
+use crate::{Encrypted, Envelope, MultiEnvelope, Plaintext, ReEncryptionKey, BLOCK_SIZE_BYTE, MESSAGE_BLOCKS_COUNT};
+use alloc::vec::Vec;
 use collar::CollectArray;
+use rand::{CryptoRng, Rng};
 
+#[cfg(feature = "std")]
 pub fn encrypt(input: &Plaintext, key: &Key) -> Encrypted {
-    let aont = AONT::from_message(input);
+    encrypt_with_rng(input, key, &mut rand::thread_rng())
+}
+
+pub fn encrypt_with_rng<R: CryptoRng + Rng + ?Sized>(input: &Plaintext, key: &Key, rng: &mut R) -> Encrypted {
+    Scheme::new().encrypt_with_rng(input, key, rng)
+}
+
+#[cfg(feature = "std")]
+pub fn encrypt_multi(input: &Plaintext, keys: &[&Key]) -> Result<MultiEnvelope, Error> {
+    encrypt_multi_with_rng(input, keys, &mut rand::thread_rng())
+}
+
+pub fn encrypt_multi_with_rng<R: CryptoRng + Rng + ?Sized>(input: &Plaintext, keys: &[&Key], rng: &mut R) -> Result<MultiEnvelope, Error> {
+    Scheme::new().encrypt_multi_with_rng(input, keys, rng)
+}
+
+fn encrypt_aont(aont: &AONT, key: &Key) -> Encrypted {
     let p1 = key.p1();
     let p2 = key.p2();
     let p3 = key.p3();
-    let aont_blocks: [&[u8; BLOCK_SIZE_BYTE]; MESSAGE_BLOCKS_COUNT + 1] = aont.encrypted.chunks_exact(BLOCK_SIZE_BYTE).map(|block| block.try_into().unwrap()).collect_array();
-    let aont_permuted_blocks = permute_block_set(&aont_blocks, &p3);
+    let aont_blocks: [[u8; BLOCK_SIZE_BYTE]; MESSAGE_BLOCKS_COUNT + 1] = aont.encrypted.chunks_exact(BLOCK_SIZE_BYTE).map(|block| block.try_into().unwrap()).collect_array();
+    let aont_permuted_blocks = p3.apply_blocks(&aont_blocks);
     let mut output = [0u8; (MESSAGE_BLOCKS_COUNT + 1) * BLOCK_SIZE_BYTE];
-    let c0: [u8; BLOCK_SIZE_BYTE] = xor_array::<BLOCK_SIZE_BYTE>(&permute_block::<BLOCK_SIZE_BITS>(aont_permuted_blocks[0], &p1), &permute_block::<BLOCK_SIZE_BITS>(&key.kx(), &p2));
+    let c0: [u8; BLOCK_SIZE_BYTE] = xor_array::<BLOCK_SIZE_BYTE>(&p1.apply_bits(&aont_permuted_blocks[0]), &p2.apply_bits(&key.kx()));
     output[0..BLOCK_SIZE_BYTE].copy_from_slice(&c0);
     for i in 1..=MESSAGE_BLOCKS_COUNT {
         let previous_encrypted_block: &[u8; BLOCK_SIZE_BYTE] = &output[(i-1) * BLOCK_SIZE_BYTE..i * BLOCK_SIZE_BYTE].try_into().unwrap();
-        let ci: [u8; BLOCK_SIZE_BYTE] = xor_array::<BLOCK_SIZE_BYTE>(&permute_block::<BLOCK_SIZE_BITS>(aont_permuted_blocks[i], &p1),
-                                                                     &permute_block::<BLOCK_SIZE_BITS>(previous_encrypted_block, &p2));
+        let ci: [u8; BLOCK_SIZE_BYTE] = xor_array::<BLOCK_SIZE_BYTE>(&p1.apply_bits(&aont_permuted_blocks[i]),
+                                                                     &p2.apply_bits(previous_encrypted_block));
         output[i * BLOCK_SIZE_BYTE..(i + 1) * BLOCK_SIZE_BYTE].copy_from_slice(&ci);
     }
     output
 }
 
 pub fn decrypt(encrypted: &Encrypted, key: &Key) -> Plaintext {
+    Scheme::new().decrypt(encrypted, key)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Scheme {
+    aont: AontKind,
+}
+
+impl Scheme {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_aont(mut self, aont: AontKind) -> Self {
+        self.aont = aont;
+        self
+    }
+
+    pub fn aont(&self) -> AontKind {
+        self.aont
+    }
+
+    #[cfg(feature = "std")]
+    pub fn encrypt(&self, input: &Plaintext, key: &Key) -> Encrypted {
+        self.encrypt_with_rng(input, key, &mut rand::thread_rng())
+    }
+
+    pub fn encrypt_with_rng<R: CryptoRng + Rng + ?Sized>(&self, input: &Plaintext, key: &Key, rng: &mut R) -> Encrypted {
+        encrypt_aont(&AONT::from_message(self.aont, key.aont_hash(), input, rng), key)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn encrypt_envelope(&self, input: &Plaintext, key: &Key) -> Envelope {
+        self.encrypt_envelope_with_rng(input, key, &mut rand::thread_rng())
+    }
+
+    pub fn encrypt_envelope_with_rng<R: CryptoRng + Rng + ?Sized>(&self, input: &Plaintext, key: &Key, rng: &mut R) -> Envelope {
+        Envelope::new(key.fingerprint(), 0, self.encrypt_with_rng(input, key, rng)).with_aont(self.aont).with_aont_hash(key.aont_hash())
+    }
+
+    #[cfg(feature = "std")]
+    pub fn encrypt_multi(&self, input: &Plaintext, keys: &[&Key]) -> Result<MultiEnvelope, Error> {
+        self.encrypt_multi_with_rng(input, keys, &mut rand::thread_rng())
+    }
+
+    // Recipients share one AONT per hash, each under the hash of its own key.
+    pub fn encrypt_multi_with_rng<R: CryptoRng + Rng + ?Sized>(&self, input: &Plaintext, keys: &[&Key], rng: &mut R) -> Result<MultiEnvelope, Error> {
+        if keys.len() > MAX_RECIPIENTS {
+            return Err(Error::InvalidFormat("too many recipients"));
+        }
+        let mut aonts: Vec<(AontHash, AONT)> = Vec::new();
+        let mut recipients = Vec::new();
+        for key in keys {
+            let aont = match aonts.iter().position(|(hash, _)| *hash == key.aont_hash()) {
+                Some(index) => &aonts[index].1,
+                None => {
+                    aonts.push((key.aont_hash(), AONT::from_message(self.aont, key.aont_hash(), input, rng)));
+                    &aonts.last().unwrap().1
+                }
+            };
+            recipients.push((key.fingerprint(), key.aont_hash(), encrypt_aont(aont, key)));
+        }
+        Ok(MultiEnvelope::new(self.aont, recipients))
+    }
+
+    pub fn decrypt(&self, encrypted: &Encrypted, key: &Key) -> Plaintext {
+        self.decrypt_with_hash(encrypted, key, key.aont_hash())
+    }
+
+    pub(crate) fn decrypt_with_hash(&self, encrypted: &Encrypted, key: &Key, hash: AontHash) -> Plaintext {
+        decrypt_aont(encrypted, key).retrieve_message(self.aont, hash)
+    }
+}
+
+fn decrypt_aont(encrypted: &Encrypted, key: &Key) -> AONT {
     let p1 = key.p1();
     let p2 = key.p2();
     let p3 = key.p3();
     let mut permuted_aont = [0u8; (MESSAGE_BLOCKS_COUNT + 1) * BLOCK_SIZE_BYTE];
-    let mp0: [u8; BLOCK_SIZE_BYTE] = depermute_block::<BLOCK_SIZE_BITS>(&xor_array::<BLOCK_SIZE_BYTE>(encrypted[0..BLOCK_SIZE_BYTE].try_into().unwrap(), &permute_block::<BLOCK_SIZE_BITS>(&key.kx(), &p2)), &p1);
+    let mp0: [u8; BLOCK_SIZE_BYTE] = p1.apply_bits_inverse(&xor_array::<BLOCK_SIZE_BYTE>(encrypted[0..BLOCK_SIZE_BYTE].try_into().unwrap(), &p2.apply_bits(&key.kx())));
     permuted_aont[0..BLOCK_SIZE_BYTE].copy_from_slice(&mp0);
     for i in 1..=MESSAGE_BLOCKS_COUNT {
         let previous_encrypted_block: &[u8; BLOCK_SIZE_BYTE] = &encrypted[(i-1) * BLOCK_SIZE_BYTE..i * BLOCK_SIZE_BYTE].try_into().unwrap();
         let ci: [u8; BLOCK_SIZE_BYTE] = encrypted[i * BLOCK_SIZE_BYTE..(i + 1) * BLOCK_SIZE_BYTE].try_into().unwrap();
-        let mpi: [u8; BLOCK_SIZE_BYTE] = depermute_block::<BLOCK_SIZE_BITS>(&xor_array::<BLOCK_SIZE_BYTE>(&ci, &permute_block::<BLOCK_SIZE_BITS>(previous_encrypted_block, &p2)), &p1);
+        let mpi: [u8; BLOCK_SIZE_BYTE] = p1.apply_bits_inverse(&xor_array::<BLOCK_SIZE_BYTE>(&ci, &p2.apply_bits(previous_encrypted_block)));
         permuted_aont[i * BLOCK_SIZE_BYTE..(i + 1) * BLOCK_SIZE_BYTE].copy_from_slice(&mpi);
     }
-    let permuted_aont: [&[u8; BLOCK_SIZE_BYTE]; MESSAGE_BLOCKS_COUNT + 1] = permuted_aont.chunks_exact(BLOCK_SIZE_BYTE).map(|block| block.try_into().unwrap()).collect_array();
-    let aont_bytes: [u8; (MESSAGE_BLOCKS_COUNT + 1) * BLOCK_SIZE_BYTE] = depermute_block_set(&permuted_aont, &p3).map(|block| *block).iter().flatten().map(|b| *b).collect_array();
-    let aont = AONT::new(&aont_bytes);
-    aont.retrieve_message()
+    let permuted_aont: [[u8; BLOCK_SIZE_BYTE]; MESSAGE_BLOCKS_COUNT + 1] = permuted_aont.chunks_exact(BLOCK_SIZE_BYTE).map(|block| block.try_into().unwrap()).collect_array();
+    let aont_bytes: [u8; (MESSAGE_BLOCKS_COUNT + 1) * BLOCK_SIZE_BYTE] = p3.apply_blocks_inverse(&permuted_aont).iter().flatten().copied().collect_array();
+    AONT::new(&aont_bytes)
 }
 
 pub fn re_encrypt(encrypted: &Encrypted, reencryption_key: &ReEncryptionKey) -> Encrypted {
@@ -50,42 +144,44 @@ pub fn re_encrypt(encrypted: &Encrypted, reencryption_key: &ReEncryptionKey) ->
     let cp3 = reencryption_key.cp3();
     let mut reperm_encrypted_blocks = [0u8; (MESSAGE_BLOCKS_COUNT + 1) * BLOCK_SIZE_BYTE];
     let c0: [u8; BLOCK_SIZE_BYTE] = encrypted[0..BLOCK_SIZE_BYTE].try_into().unwrap();
-    let c0_prime: [u8; BLOCK_SIZE_BYTE] = permute_block(&xor_array(&c0, &permute_block(&reencryption_key.old_kx(), &old_p2)), &cp1);
+    let c0_prime: [u8; BLOCK_SIZE_BYTE] = cp1.apply_bits(&xor_array(&c0, &old_p2.apply_bits(&reencryption_key.old_kx())));
     reperm_encrypted_blocks[0..BLOCK_SIZE_BYTE].copy_from_slice(&c0_prime);
     for i in 1..=MESSAGE_BLOCKS_COUNT {
         let previous_ci: [u8; BLOCK_SIZE_BYTE] = encrypted[(i - 1) * BLOCK_SIZE_BYTE..i * BLOCK_SIZE_BYTE].try_into().unwrap();
         let ci: [u8; BLOCK_SIZE_BYTE] = encrypted[i * BLOCK_SIZE_BYTE..(i + 1) * BLOCK_SIZE_BYTE].try_into().unwrap();
-        let ci_prime: [u8; BLOCK_SIZE_BYTE] = permute_block(&xor_array(&ci, &permute_block(&previous_ci, &old_p2)), &cp1);
+        let ci_prime: [u8; BLOCK_SIZE_BYTE] = cp1.apply_bits(&xor_array(&ci, &old_p2.apply_bits(&previous_ci)));
         reperm_encrypted_blocks[i * BLOCK_SIZE_BYTE..(i + 1) * BLOCK_SIZE_BYTE].copy_from_slice(&ci_prime);
     }
 
-    let splitted_reperm_encrypted_blocks: [&[u8; BLOCK_SIZE_BYTE]; MESSAGE_BLOCKS_COUNT + 1] = reperm_encrypted_blocks
+    let splitted_reperm_encrypted_blocks: [[u8; BLOCK_SIZE_BYTE]; MESSAGE_BLOCKS_COUNT + 1] = reperm_encrypted_blocks
         .chunks_exact(BLOCK_SIZE_BYTE)
         .map(|block| block.try_into().unwrap())
         .collect_array();
-    let correct_permuted_blocks: [u8; (MESSAGE_BLOCKS_COUNT + 1) * BLOCK_SIZE_BYTE] = permute_block_set(&splitted_reperm_encrypted_blocks, &cp3)
-        .map(|block| *block)
+    let correct_permuted_blocks: [u8; (MESSAGE_BLOCKS_COUNT + 1) * BLOCK_SIZE_BYTE] = cp3.apply_blocks(&splitted_reperm_encrypted_blocks)
         .iter()
         .flatten()
-        .map(|b| *b)
+        .copied()
         .collect_array();
 
     let mut output = [0u8; (MESSAGE_BLOCKS_COUNT + 1) * BLOCK_SIZE_BYTE];
     let c0: [u8; BLOCK_SIZE_BYTE] = correct_permuted_blocks[0..BLOCK_SIZE_BYTE].try_into().unwrap();
-    let new_c0: [u8; BLOCK_SIZE_BYTE] = xor_array(&c0, &permute_block(&reencryption_key.new_kx(), &new_p2));
+    let new_c0: [u8; BLOCK_SIZE_BYTE] = xor_array(&c0, &new_p2.apply_bits(&reencryption_key.new_kx()));
     output[0..BLOCK_SIZE_BYTE].copy_from_slice(&new_c0);
     for i in 1..=MESSAGE_BLOCKS_COUNT {
         let previous_ci: [u8; BLOCK_SIZE_BYTE] = output[(i - 1) * BLOCK_SIZE_BYTE..i * BLOCK_SIZE_BYTE].try_into().unwrap();
         let ci: [u8; BLOCK_SIZE_BYTE] = correct_permuted_blocks[i * BLOCK_SIZE_BYTE..(i + 1) * BLOCK_SIZE_BYTE].try_into().unwrap();
-        let new_ci: [u8; BLOCK_SIZE_BYTE] = xor_array(&ci, &permute_block(&previous_ci, &new_p2));
+        let new_ci: [u8; BLOCK_SIZE_BYTE] = xor_array(&ci, &new_p2.apply_bits(&previous_ci));
         output[i * BLOCK_SIZE_BYTE..(i + 1) * BLOCK_SIZE_BYTE].copy_from_slice(&new_ci);
     }
     output
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod tests {
-    use crate::{decrypt, encrypt, re_encrypt, Key, ReEncryptionKey};
+    use crate::error::Error;
+    use crate::{decrypt, encrypt, encrypt_with_rng, re_encrypt, AontHash, AontKind, Key, ReEncryptionKey, Scheme};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
 
     #[test]
     fn test_encryption_reencryption() {
@@ -96,9 +192,110 @@ mod tests {
         assert_eq!(message, &decrypted);
 
         let key2 = Key::generate();
-        let re_encryption_key = ReEncryptionKey::generate(&key1, &key2);
+        let re_encryption_key = ReEncryptionKey::generate(&key1, &key2).unwrap();
         let re_encrypted = re_encrypt(&encrypted, &re_encryption_key);
         let re_decrypted = decrypt(&re_encrypted, &key2);
         assert_eq!(message, &re_decrypted);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_aont_kinds_reencryption() {
+        let message = b"les sanglots longs des violons !";
+        for kind in [AontKind::HashXor, AontKind::PackageTransform, AontKind::Oaep] {
+            let scheme = Scheme::new().with_aont(kind);
+            let key1 = Key::generate();
+            let key2 = Key::generate();
+            let key3 = Key::generate();
+            let encrypted = scheme.encrypt(message, &key1);
+            assert_eq!(message, &scheme.decrypt(&encrypted, &key1));
+
+            let re_encryption_key_12 = ReEncryptionKey::generate(&key1, &key2).unwrap();
+            let re_encrypted = re_encrypt(&encrypted, &re_encryption_key_12);
+            assert_eq!(message, &scheme.decrypt(&re_encrypted, &key2));
+
+            let re_encryption_key_23 = ReEncryptionKey::generate(&key2, &key3).unwrap();
+            let composed = re_encrypt(&encrypted, &ReEncryptionKey::compose(&re_encryption_key_12, &re_encryption_key_23).unwrap());
+            assert_eq!(message, &scheme.decrypt(&composed, &key3));
+        }
+        assert_eq!(Scheme::new().aont(), AontKind::HashXor);
+    }
+
+    #[test]
+    fn test_aont_hashes_reencryption() {
+        let message = b"les sanglots longs des violons !";
+        for aont_hash in [AontHash::Sha3_256, AontHash::Sha256, AontHash::Blake3, AontHash::Shake128] {
+            let key1 = Key::generate().with_aont_hash(aont_hash);
+            let key2 = Key::generate().with_aont_hash(aont_hash);
+            let encrypted = encrypt(message, &key1);
+            assert_eq!(message, &decrypt(&encrypted, &key1));
+            let re_encrypted = re_encrypt(&encrypted, &ReEncryptionKey::generate(&key1, &key2).unwrap());
+            assert_eq!(message, &decrypt(&re_encrypted, &key2));
+
+            // Raw ciphertexts are decrypted with the hash of the decrypting key, so re-encrypting to a key using
+            // another hash is refused rather than producing ciphertexts that decrypt to garbage.
+            for other_hash in [AontHash::Sha3_256, AontHash::Sha256, AontHash::Blake3, AontHash::Shake128] {
+                if other_hash != aont_hash {
+                    let other_key = Key::generate().with_aont_hash(other_hash);
+                    assert_eq!(ReEncryptionKey::generate(&key1, &other_key), Err(Error::AontHashMismatch { expected: aont_hash, found: other_hash }));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_composed_reencryption() {
+        let message = b"les sanglots longs des violons !";
+        let key1 = Key::generate();
+        let key2 = Key::generate();
+        let key3 = Key::generate();
+        let re_encryption_key_12 = ReEncryptionKey::generate(&key1, &key2).unwrap();
+        let re_encryption_key_23 = ReEncryptionKey::generate(&key2, &key3).unwrap();
+        let re_encryption_key_13 = ReEncryptionKey::compose(&re_encryption_key_12, &re_encryption_key_23).unwrap();
+
+        let encrypted = encrypt(message, &key1);
+        let two_steps = re_encrypt(&re_encrypt(&encrypted, &re_encryption_key_12), &re_encryption_key_23);
+        let one_step = re_encrypt(&encrypted, &re_encryption_key_13);
+        assert_eq!(two_steps, one_step);
+        assert_eq!(message, &decrypt(&one_step, &key3));
+
+        let direct = re_encrypt(&encrypted, &ReEncryptionKey::generate(&key1, &key3).unwrap());
+        assert_eq!(direct, one_step);
+
+        // Composing only makes sense when the first key's target is the second key's source.
+        assert_eq!(
+            ReEncryptionKey::compose(&re_encryption_key_23, &re_encryption_key_12),
+            Err(Error::KeyMismatch { expected: key3.fingerprint(), found: key1.fingerprint() })
+        );
+    }
+
+    #[test]
+    fn test_inverse_reencryption() {
+        let message = b"les sanglots longs des violons !";
+        let key1 = Key::generate();
+        let key2 = Key::generate();
+        let re_encryption_key = ReEncryptionKey::generate(&key1, &key2).unwrap();
+        let inverse_key = re_encryption_key.inverse();
+
+        let encrypted = encrypt(message, &key2);
+        let re_encrypted = re_encrypt(&encrypted, &inverse_key);
+        assert_eq!(message, &decrypt(&re_encrypted, &key1));
+
+        let direct = re_encrypt(&encrypted, &ReEncryptionKey::generate(&key2, &key1).unwrap());
+        assert_eq!(direct, re_encrypted);
+
+        let encrypted = encrypt(message, &key1);
+        let round_trip = re_encrypt(&re_encrypt(&encrypted, &re_encryption_key), &inverse_key);
+        assert_eq!(encrypted, round_trip);
+    }
+
+    #[test]
+    fn test_encryption_with_external_rng() {
+        let message = b"les sanglots longs des violons !";
+        let key = Key::generate_with_rng(&mut StdRng::seed_from_u64(42));
+        assert_eq!(key, Key::generate_with_rng(&mut StdRng::seed_from_u64(42)));
+
+        let encrypted = encrypt_with_rng(message, &key, &mut StdRng::seed_from_u64(7));
+        assert_eq!(encrypted, encrypt_with_rng(message, &key, &mut StdRng::seed_from_u64(7)));
+        assert_eq!(message, &decrypt(&encrypted, &key));
+    }
+}
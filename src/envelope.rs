@@ -0,0 +1,334 @@
+use crate::error::Error;
+use crate::serialization::{write_header, write_scheme_parameters, Reader, AONT_FORMAT_VERSION, HASH_FORMAT_VERSION};
+use crate::{re_encrypt, AontHash, AontKind, Encrypted, Fingerprint, Key, Plaintext, ReEncryptionKey, Scheme};
+use alloc::vec::Vec;
+
+pub(crate) const ENVELOPE_MAGIC: &[u8; 4] = b"SPEV";
+pub(crate) const MULTI_ENVELOPE_MAGIC: &[u8; 4] = b"SPME";
+// Multi-envelopes count their recipients on 16 bits.
+pub(crate) const MAX_RECIPIENTS: usize = u16::MAX as usize;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Envelope {
+    key_fingerprint: Fingerprint,
+    epoch: u64,
+    aont: AontKind,
+    aont_hash: AontHash,
+    ciphertext: Encrypted,
+}
+
+impl Envelope {
+    pub fn new(key_fingerprint: Fingerprint, epoch: u64, ciphertext: Encrypted) -> Self {
+        Self { key_fingerprint, epoch, aont: AontKind::default(), aont_hash: AontHash::default(), ciphertext }
+    }
+
+    pub fn with_aont(mut self, aont: AontKind) -> Self {
+        self.aont = aont;
+        self
+    }
+
+    pub fn with_aont_hash(mut self, aont_hash: AontHash) -> Self {
+        self.aont_hash = aont_hash;
+        self
+    }
+
+    pub fn key_fingerprint(&self) -> Fingerprint {
+        self.key_fingerprint
+    }
+
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    pub fn aont(&self) -> AontKind {
+        self.aont
+    }
+
+    pub fn aont_hash(&self) -> AontHash {
+        self.aont_hash
+    }
+
+    pub fn ciphertext(&self) -> &Encrypted {
+        &self.ciphertext
+    }
+
+    pub fn decrypt(&self, key: &Key) -> Result<Plaintext, Error> {
+        if self.key_fingerprint != key.fingerprint() {
+            return Err(Error::KeyMismatch { expected: self.key_fingerprint, found: key.fingerprint() });
+        }
+        Ok(Scheme::new().with_aont(self.aont).decrypt_with_hash(&self.ciphertext, key, self.aont_hash))
+    }
+
+    pub fn re_encrypt(&self, re_encryption_key: &ReEncryptionKey) -> Result<Envelope, Error> {
+        if self.key_fingerprint != re_encryption_key.source_fingerprint() {
+            return Err(Error::KeyMismatch { expected: re_encryption_key.source_fingerprint(), found: self.key_fingerprint });
+        }
+        Ok(Envelope::new(re_encryption_key.target_fingerprint(), self.epoch, re_encrypt(&self.ciphertext, re_encryption_key)).with_aont(self.aont).with_aont_hash(self.aont_hash))
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut output = Vec::new();
+        write_header(&mut output, ENVELOPE_MAGIC);
+        write_scheme_parameters(&mut output);
+        output.push(self.aont.to_byte());
+        output.push(self.aont_hash.to_byte());
+        output.extend_from_slice(&self.key_fingerprint);
+        output.extend_from_slice(&self.epoch.to_be_bytes());
+        output.extend_from_slice(&self.ciphertext);
+        output
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader::new(bytes);
+        let version = reader.read_header(ENVELOPE_MAGIC)?;
+        reader.read_scheme_parameters()?;
+        let aont = if version >= AONT_FORMAT_VERSION { AontKind::from_byte(reader.read_u8()?)? } else { AontKind::HashXor };
+        let aont_hash = if version >= HASH_FORMAT_VERSION { AontHash::from_byte(reader.read_u8()?)? } else { AontHash::Sha3_256 };
+        let key_fingerprint = reader.read_array()?;
+        let epoch = reader.read_u64()?;
+        let ciphertext = reader.read_array()?;
+        reader.finish()?;
+        Ok(Self { key_fingerprint, epoch, aont, aont_hash, ciphertext })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultiEnvelope {
+    aont: AontKind,
+    recipients: Vec<(Fingerprint, AontHash, Encrypted)>,
+}
+
+impl MultiEnvelope {
+    pub(crate) fn new(aont: AontKind, recipients: Vec<(Fingerprint, AontHash, Encrypted)>) -> Self {
+        Self { aont, recipients }
+    }
+
+    pub fn aont(&self) -> AontKind {
+        self.aont
+    }
+
+    pub fn recipients(&self) -> Vec<Fingerprint> {
+        self.recipients.iter().map(|(fingerprint, _, _)| *fingerprint).collect()
+    }
+
+    pub fn aont_hash(&self, key_fingerprint: Fingerprint) -> Option<AontHash> {
+        self.recipients.iter().find(|(fingerprint, _, _)| *fingerprint == key_fingerprint).map(|(_, aont_hash, _)| *aont_hash)
+    }
+
+    pub fn ciphertext(&self, key_fingerprint: Fingerprint) -> Option<&Encrypted> {
+        self.recipients.iter().find(|(fingerprint, _, _)| *fingerprint == key_fingerprint).map(|(_, _, ciphertext)| ciphertext)
+    }
+
+    pub fn decrypt(&self, key: &Key) -> Result<Plaintext, Error> {
+        let (_, aont_hash, ciphertext) = self
+            .recipients
+            .iter()
+            .find(|(fingerprint, _, _)| *fingerprint == key.fingerprint())
+            .ok_or(Error::UnknownRecipient(key.fingerprint()))?;
+        Ok(Scheme::new().with_aont(self.aont).decrypt_with_hash(ciphertext, key, *aont_hash))
+    }
+
+    // The target must not be a recipient already, otherwise it would get two ciphertexts.
+    pub fn re_encrypt(&mut self, re_encryption_key: &ReEncryptionKey) -> Result<(), Error> {
+        let source = re_encryption_key.source_fingerprint();
+        let target = re_encryption_key.target_fingerprint();
+        let index = self.recipients.iter().position(|(fingerprint, _, _)| *fingerprint == source).ok_or(Error::UnknownRecipient(source))?;
+        if target != source && self.recipients.iter().any(|(fingerprint, _, _)| *fingerprint == target) {
+            return Err(Error::KeyMismatch { expected: source, found: target });
+        }
+        let (fingerprint, _, ciphertext) = &mut self.recipients[index];
+        *fingerprint = target;
+        *ciphertext = re_encrypt(ciphertext, re_encryption_key);
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut output = Vec::new();
+        write_header(&mut output, MULTI_ENVELOPE_MAGIC);
+        write_scheme_parameters(&mut output);
+        output.push(self.aont.to_byte());
+        // encrypt_multi caps the recipients to MAX_RECIPIENTS and re_encrypt never adds any.
+        output.extend_from_slice(&u16::try_from(self.recipients.len()).unwrap().to_be_bytes());
+        for (fingerprint, aont_hash, ciphertext) in &self.recipients {
+            output.extend_from_slice(fingerprint);
+            output.push(aont_hash.to_byte());
+            output.extend_from_slice(ciphertext);
+        }
+        output
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader::new(bytes);
+        let version = reader.read_header(MULTI_ENVELOPE_MAGIC)?;
+        reader.read_scheme_parameters()?;
+        let aont = if version >= HASH_FORMAT_VERSION { AontKind::from_byte(reader.read_u8()?)? } else { AontKind::HashXor };
+        let recipients_count = reader.read_u16()?;
+        let recipients = (0..recipients_count)
+            .map(|_| {
+                let fingerprint = reader.read_array()?;
+                let aont_hash = if version >= HASH_FORMAT_VERSION { AontHash::from_byte(reader.read_u8()?)? } else { AontHash::Sha3_256 };
+                Ok((fingerprint, aont_hash, reader.read_array()?))
+            })
+            .collect::<Result<_, Error>>()?;
+        reader.finish()?;
+        Ok(Self { aont, recipients })
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::error::Error;
+    use crate::serialization::FORMAT_VERSION;
+    use crate::{encrypt, encrypt_multi, AontHash, AontKind, Envelope, Key, MultiEnvelope, ReEncryptionKey, Scheme};
+
+    #[test]
+    fn test_envelope_serialization() {
+        let key = Key::generate();
+        let envelope = Envelope::new(key.fingerprint(), 3, encrypt(b"les sanglots longs des violons !", &key));
+        let bytes = envelope.to_bytes();
+        assert_eq!(Envelope::from_bytes(&bytes), Ok(envelope));
+        assert_eq!(Envelope::from_bytes(&bytes[..bytes.len() - 1]), Err(Error::InvalidFormat("truncated input")));
+        assert_eq!(Envelope::from_bytes(&[bytes.as_slice(), &[0]].concat()), Err(Error::InvalidFormat("trailing bytes")));
+    }
+
+    #[test]
+    fn test_envelope_aont_kinds() {
+        let message = b"les sanglots longs des violons !";
+        for kind in [AontKind::HashXor, AontKind::PackageTransform, AontKind::Oaep] {
+            let key1 = Key::generate();
+            let key2 = Key::generate();
+            let envelope = Scheme::new().with_aont(kind).encrypt_envelope(message, &key1);
+            assert_eq!(envelope.aont(), kind);
+            let envelope = Envelope::from_bytes(&envelope.to_bytes()).unwrap();
+            assert_eq!(envelope.aont(), kind);
+            assert_eq!(envelope.decrypt(&key1), Ok(*message));
+            assert_eq!(envelope.decrypt(&key2), Err(Error::KeyMismatch { expected: key1.fingerprint(), found: key2.fingerprint() }));
+
+            let re_encrypted = envelope.re_encrypt(&ReEncryptionKey::generate(&key1, &key2).unwrap()).unwrap();
+            assert_eq!(re_encrypted.aont(), kind);
+            assert_eq!(Envelope::from_bytes(&re_encrypted.to_bytes()).unwrap().decrypt(&key2), Ok(*message));
+        }
+    }
+
+    #[test]
+    fn test_envelope_without_aont_kind() {
+        // Version 3 envelopes end their header with the AONT kind, version 2 ones with the scheme parameters.
+        let key = Key::generate();
+        let envelope = Envelope::new(key.fingerprint(), 3, encrypt(b"les sanglots longs des violons !", &key)).with_aont(AontKind::Oaep);
+        let mut bytes = envelope.to_bytes();
+        bytes[4] = FORMAT_VERSION - 1;
+        bytes.remove(10);
+        assert_eq!(Envelope::from_bytes(&bytes), Ok(envelope.clone()));
+        bytes[4] = FORMAT_VERSION - 2;
+        bytes.remove(9);
+        assert_eq!(Envelope::from_bytes(&bytes), Ok(envelope.with_aont(AontKind::HashXor)));
+    }
+
+    #[test]
+    fn test_envelope_aont_hashes() {
+        let message = b"les sanglots longs des violons !";
+        for aont_hash in [AontHash::Sha3_256, AontHash::Sha256, AontHash::Blake3, AontHash::Shake128] {
+            let key1 = Key::generate().with_aont_hash(aont_hash);
+            let key2 = Key::generate().with_aont_hash(aont_hash);
+            let envelope = Scheme::new().with_aont(AontKind::PackageTransform).encrypt_envelope(message, &key1);
+            assert_eq!(envelope.aont_hash(), aont_hash);
+            let envelope = Envelope::from_bytes(&envelope.to_bytes()).unwrap();
+            assert_eq!(envelope.aont_hash(), aont_hash);
+            assert_eq!(envelope.decrypt(&key1), Ok(*message));
+
+            let re_encrypted = envelope.re_encrypt(&ReEncryptionKey::generate(&key1, &key2).unwrap()).unwrap();
+            assert_eq!(re_encrypted.aont_hash(), aont_hash);
+            assert_eq!(Envelope::from_bytes(&re_encrypted.to_bytes()).unwrap().decrypt(&key2), Ok(*message));
+
+            // The envelope records its hash, so the key's own choice does not matter for decryption.
+            assert_eq!(envelope.decrypt(&key1.clone().with_aont_hash(AontHash::Sha256)), Ok(*message));
+        }
+    }
+
+    #[test]
+    fn test_envelope_re_encryption() {
+        let key1 = Key::generate();
+        let key2 = Key::generate();
+        let re_encryption_key = ReEncryptionKey::generate(&key1, &key2).unwrap();
+        let envelope = Envelope::new(key1.fingerprint(), 0, encrypt(b"les sanglots longs des violons !", &key1));
+        let re_encrypted = envelope.re_encrypt(&re_encryption_key).unwrap();
+        assert_eq!(re_encrypted.key_fingerprint(), key2.fingerprint());
+        assert_eq!(re_encrypted.re_encrypt(&re_encryption_key), Err(Error::KeyMismatch { expected: key1.fingerprint(), found: key2.fingerprint() }));
+    }
+
+    #[test]
+    fn test_multi_envelope() {
+        let message = b"les sanglots longs des violons !";
+        let key1 = Key::generate();
+        let key2 = Key::generate();
+        let key3 = Key::generate();
+        let mut multi_envelope = encrypt_multi(message, &[&key1, &key2]).unwrap();
+        assert_eq!(multi_envelope.recipients(), [key1.fingerprint(), key2.fingerprint()]);
+        assert_eq!(multi_envelope.decrypt(&key1), Ok(*message));
+        assert_eq!(multi_envelope.decrypt(&key2), Ok(*message));
+        assert_eq!(multi_envelope.decrypt(&key3), Err(Error::UnknownRecipient(key3.fingerprint())));
+        assert_eq!(MultiEnvelope::from_bytes(&multi_envelope.to_bytes()), Ok(multi_envelope.clone()));
+
+        let untouched = *multi_envelope.ciphertext(key2.fingerprint()).unwrap();
+        let re_encryption_key = ReEncryptionKey::generate(&key1, &key3).unwrap();
+        multi_envelope.re_encrypt(&re_encryption_key).unwrap();
+        assert_eq!(multi_envelope.recipients(), [key3.fingerprint(), key2.fingerprint()]);
+        assert_eq!(multi_envelope.decrypt(&key3), Ok(*message));
+        assert_eq!(multi_envelope.ciphertext(key2.fingerprint()), Some(&untouched));
+        assert_eq!(multi_envelope.re_encrypt(&re_encryption_key), Err(Error::UnknownRecipient(key1.fingerprint())));
+
+        // Re-encrypting to a key that is already a recipient would give it two ciphertexts.
+        let before = multi_envelope.clone();
+        let re_encryption_key = ReEncryptionKey::generate(&key3, &key2).unwrap();
+        assert_eq!(multi_envelope.re_encrypt(&re_encryption_key), Err(Error::KeyMismatch { expected: key3.fingerprint(), found: key2.fingerprint() }));
+        assert_eq!(multi_envelope, before);
+    }
+
+    #[test]
+    fn test_multi_envelope_recipients_limit() {
+        let message = b"les sanglots longs des violons !";
+        let key = Key::generate();
+        let keys = vec![&key; super::MAX_RECIPIENTS + 1];
+        assert_eq!(encrypt_multi(message, &keys), Err(Error::InvalidFormat("too many recipients")));
+    }
+
+    #[test]
+    fn test_multi_envelope_aont_hashes() {
+        let message = b"les sanglots longs des violons !";
+        let key1 = Key::generate().with_aont_hash(AontHash::Blake3);
+        let key2 = Key::generate().with_aont_hash(AontHash::Sha256);
+        let key3 = Key::generate();
+        let key4 = Key::generate().with_aont_hash(AontHash::Blake3);
+        let multi_envelope = Scheme::new().with_aont(AontKind::Oaep).encrypt_multi(message, &[&key1, &key2, &key3]).unwrap();
+        let mut multi_envelope = MultiEnvelope::from_bytes(&multi_envelope.to_bytes()).unwrap();
+        assert_eq!(multi_envelope.aont(), AontKind::Oaep);
+        assert_eq!(multi_envelope.aont_hash(key1.fingerprint()), Some(AontHash::Blake3));
+        assert_eq!(multi_envelope.aont_hash(key2.fingerprint()), Some(AontHash::Sha256));
+        for key in [&key1, &key2, &key3] {
+            assert_eq!(multi_envelope.decrypt(key), Ok(*message));
+        }
+
+        multi_envelope.re_encrypt(&ReEncryptionKey::generate(&key1, &key4).unwrap()).unwrap();
+        let multi_envelope = MultiEnvelope::from_bytes(&multi_envelope.to_bytes()).unwrap();
+        assert_eq!(multi_envelope.aont_hash(key4.fingerprint()), Some(AontHash::Blake3));
+        assert_eq!(multi_envelope.decrypt(&key4), Ok(*message));
+        assert_eq!(multi_envelope.decrypt(&key2), Ok(*message));
+    }
+
+    #[test]
+    fn test_legacy_multi_envelope() {
+        // Before version 4, multi-envelopes recorded neither the AONT kind nor the hashes.
+        let message = b"les sanglots longs des violons !";
+        let key1 = Key::generate();
+        let key2 = Key::generate();
+        let mut bytes = encrypt_multi(message, &[&key1, &key2]).unwrap().to_bytes();
+        bytes[4] = 3;
+        bytes.remove(9);
+        bytes.remove(19);
+        bytes.remove(19 + 8 + 36);
+        let multi_envelope = MultiEnvelope::from_bytes(&bytes).unwrap();
+        assert_eq!(multi_envelope.aont(), AontKind::HashXor);
+        assert_eq!(multi_envelope.decrypt(&key1), Ok(*message));
+        assert_eq!(multi_envelope.decrypt(&key2), Ok(*message));
+    }
+}
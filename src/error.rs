@@ -0,0 +1,50 @@
+use crate::{AontHash, Fingerprint};
+use alloc::format;
+use alloc::string::String;
+use core::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    UnknownEpoch(u64),
+    EpochMismatch { expected: u64, found: u64 },
+    KeyMismatch { expected: Fingerprint, found: Fingerprint },
+    AontHashMismatch { expected: AontHash, found: AontHash },
+    UnknownReEncryptionKey { source: Fingerprint, target: Fingerprint },
+    UnknownRecipient(Fingerprint),
+    InvalidFormat(&'static str),
+    InvalidId(String),
+    PassphraseRequired,
+    IntegrityCheckFailed,
+    Io(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::UnknownEpoch(epoch) => write!(f, "unknown epoch {}", epoch),
+            Error::EpochMismatch { expected, found } => write!(f, "expected epoch {}, found epoch {}", expected, found),
+            Error::KeyMismatch { expected, found } => write!(f, "expected key {}, found key {}", to_hex(expected), to_hex(found)),
+            Error::AontHashMismatch { expected, found } => write!(f, "expected AONT hash {:?}, found AONT hash {:?}", expected, found),
+            Error::UnknownReEncryptionKey { source, target } => write!(f, "no re-encryption key from {} to {}", to_hex(source), to_hex(target)),
+            Error::UnknownRecipient(fingerprint) => write!(f, "key {} is not a recipient", to_hex(fingerprint)),
+            Error::InvalidFormat(reason) => write!(f, "invalid format: {}", reason),
+            Error::InvalidId(id) => write!(f, "invalid id {:?}", id),
+            Error::PassphraseRequired => write!(f, "a passphrase is required"),
+            Error::IntegrityCheckFailed => write!(f, "integrity check failed"),
+            Error::Io(message) => write!(f, "I/O error: {}", message),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Error::Io(error.to_string())
+    }
+}
+
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
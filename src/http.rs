@@ -0,0 +1,114 @@
+use crate::error::Error;
+use crate::{Envelope, KeyStore, ReEncryptionKey};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{self, Read};
+use std::net::TcpListener;
+use std::sync::RwLock;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+const MAX_BODY_LENGTH: u64 = 1 << 16;
+
+#[derive(Deserialize)]
+struct ReEncryptRequest {
+    envelope: String,
+    rekey: String,
+}
+
+#[derive(Serialize)]
+struct ReEncryptResponse {
+    envelope: String,
+}
+
+#[derive(Serialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+pub struct HttpProxy {
+    server: Server,
+    re_encryption_keys: RwLock<HashMap<String, ReEncryptionKey>>,
+}
+
+impl HttpProxy {
+    pub fn new(listener: TcpListener) -> io::Result<Self> {
+        let server = Server::from_listener(listener, None).map_err(io::Error::other)?;
+        Ok(Self { server, re_encryption_keys: RwLock::new(HashMap::new()) })
+    }
+
+    pub fn load_store<S: KeyStore + ?Sized>(&self, store: &S) -> Result<(), Error> {
+        for id in store.list_re_encryption_keys()? {
+            if let Some(re_encryption_key) = store.get_re_encryption_key(&id)? {
+                self.insert(id, re_encryption_key);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn insert(&self, id: String, re_encryption_key: ReEncryptionKey) {
+        self.re_encryption_keys.write().unwrap().insert(id, re_encryption_key);
+    }
+
+    pub fn remove(&self, id: &str) -> Option<ReEncryptionKey> {
+        self.re_encryption_keys.write().unwrap().remove(id)
+    }
+
+    pub fn serve(&self) -> io::Result<()> {
+        for request in self.server.incoming_requests() {
+            // A client hanging up in the middle of its request only loses that request.
+            if let Err(e) = self.handle(request) {
+                eprintln!("cannot handle request: {}", e);
+            }
+        }
+        Ok(())
+    }
+
+    fn handle(&self, mut request: Request) -> io::Result<()> {
+        let mut body = Vec::new();
+        request.as_reader().take(MAX_BODY_LENGTH + 1).read_to_end(&mut body)?;
+        let result = if body.len() as u64 > MAX_BODY_LENGTH {
+            Err((413, format!("body longer than {} bytes", MAX_BODY_LENGTH)))
+        } else {
+            self.route(request.method(), request.url(), &body)
+        };
+        let (status, body) = match result {
+            Ok(None) => (204, String::new()),
+            Ok(Some(body)) => (200, body),
+            Err((status, error)) => (status, serde_json::to_string(&ErrorResponse { error }).unwrap()),
+        };
+        let content_type = Header::from_bytes("Content-Type", "application/json").unwrap();
+        request.respond(Response::from_string(body).with_status_code(status).with_header(content_type))
+    }
+
+    fn route(&self, method: &Method, url: &str, body: &[u8]) -> Result<Option<String>, (u16, String)> {
+        match (method, url.strip_prefix("/rekeys/")) {
+            (Method::Post, _) if url == "/reencrypt" => self.re_encrypt(body).map(Some),
+            (Method::Put, Some(id)) if !id.is_empty() => {
+                let re_encryption_key = ReEncryptionKey::from_bytes(body).map_err(bad_request)?;
+                self.insert(id.to_string(), re_encryption_key);
+                Ok(None)
+            }
+            (Method::Delete, Some(id)) => match self.remove(id) {
+                Some(_) => Ok(None),
+                None => Err((404, format!("unknown re-encryption key {}", id))),
+            },
+            _ => Err((404, format!("no route for {} {}", method, url))),
+        }
+    }
+
+    fn re_encrypt(&self, body: &[u8]) -> Result<String, (u16, String)> {
+        let request: ReEncryptRequest = serde_json::from_slice(body).map_err(|e| (400, e.to_string()))?;
+        let envelope = hex::decode(&request.envelope).map_err(|e| (400, e.to_string()))?;
+        let envelope = Envelope::from_bytes(&envelope).map_err(bad_request)?;
+        let re_encryption_keys = self.re_encryption_keys.read().unwrap();
+        let re_encryption_key = re_encryption_keys
+            .get(&request.rekey)
+            .ok_or_else(|| (404, format!("unknown re-encryption key {}", request.rekey)))?;
+        let re_encrypted = envelope.re_encrypt(re_encryption_key).map_err(bad_request)?;
+        Ok(serde_json::to_string(&ReEncryptResponse { envelope: hex::encode(re_encrypted.to_bytes()) }).unwrap())
+    }
+}
+
+fn bad_request(error: Error) -> (u16, String) {
+    (400, error.to_string())
+}
@@ -0,0 +1,146 @@
+use crate::error::Error;
+use crate::serialization::{Reader, ASSOCIATED_DATA_FORMAT_VERSION, FORMAT_VERSION};
+use crate::{encrypt, Envelope, Key, Plaintext, ReEncryptionKey};
+use chacha20poly1305::aead::{Aead, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, KeyInit};
+use rand::Rng;
+
+pub(crate) const SEALED_OBJECT_MAGIC: &[u8; 4] = b"SPSO";
+const NONCE_LENGTH: usize = 12;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SealedObject {
+    version: u8,
+    data_key: Envelope,
+    nonce: [u8; NONCE_LENGTH],
+    payload: Vec<u8>,
+}
+
+pub fn seal(payload: &[u8], key: &Key) -> SealedObject {
+    let mut rng = rand::thread_rng();
+    let mut data_key: Plaintext = [0u8; size_of::<Plaintext>()];
+    let mut nonce = [0u8; NONCE_LENGTH];
+    rng.fill(&mut data_key);
+    rng.fill(&mut nonce);
+    let associated_data = associated_data(FORMAT_VERSION, &nonce);
+    let payload = ChaCha20Poly1305::new(&data_key.into()).encrypt(&nonce.into(), Payload { msg: payload, aad: &associated_data }).unwrap();
+    SealedObject {
+        version: FORMAT_VERSION,
+        data_key: Envelope::new(key.fingerprint(), 0, encrypt(&data_key, key)).with_aont_hash(key.aont_hash()),
+        nonce,
+        payload,
+    }
+}
+
+pub fn open(sealed_object: &SealedObject, key: &Key) -> Result<Vec<u8>, Error> {
+    let data_key = sealed_object.data_key.decrypt(key)?;
+    let associated_data = associated_data(sealed_object.version, &sealed_object.nonce);
+    ChaCha20Poly1305::new(&data_key.into())
+        .decrypt(&sealed_object.nonce.into(), Payload { msg: &sealed_object.payload, aad: &associated_data })
+        .map_err(|_| Error::IntegrityCheckFailed)
+}
+
+// The header and nonce are authenticated with the payload, but not the data key envelope: re-encryption replaces it,
+// and it is bound to the payload anyway since only its data key opens it.
+fn associated_data(version: u8, nonce: &[u8; NONCE_LENGTH]) -> Vec<u8> {
+    if version < ASSOCIATED_DATA_FORMAT_VERSION {
+        return Vec::new();
+    }
+    [SEALED_OBJECT_MAGIC.as_slice(), &[version], nonce].concat()
+}
+
+impl SealedObject {
+    pub fn data_key(&self) -> &Envelope {
+        &self.data_key
+    }
+
+    // Only the data key is replaced, the payload is left where it is.
+    pub fn re_encrypt(&mut self, re_encryption_key: &ReEncryptionKey) -> Result<(), Error> {
+        self.data_key = self.data_key.re_encrypt(re_encryption_key)?;
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let data_key = self.data_key.to_bytes();
+        let mut output = Vec::new();
+        output.extend_from_slice(SEALED_OBJECT_MAGIC);
+        output.push(self.version);
+        output.extend_from_slice(&(data_key.len() as u16).to_be_bytes());
+        output.extend_from_slice(&data_key);
+        output.extend_from_slice(&self.nonce);
+        output.extend_from_slice(&self.payload);
+        output
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader::new(bytes);
+        let version = reader.read_header(SEALED_OBJECT_MAGIC)?;
+        let data_key_length = reader.read_u16()? as usize;
+        let data_key = Envelope::from_bytes(reader.read_slice(data_key_length)?)?;
+        let nonce = reader.read_array()?;
+        let payload = reader.read_remaining().to_vec();
+        Ok(Self { version, data_key, nonce, payload })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{open, seal, SealedObject, NONCE_LENGTH};
+    use crate::error::Error;
+    use crate::serialization::ASSOCIATED_DATA_FORMAT_VERSION;
+    use crate::{encrypt, Envelope, Key, Plaintext, ReEncryptionKey};
+    use chacha20poly1305::aead::Aead;
+    use chacha20poly1305::{ChaCha20Poly1305, KeyInit};
+
+    #[test]
+    fn test_seal_open() {
+        let payload: Vec<u8> = (0..100_000u32).map(|i| i as u8).collect();
+        let key = Key::generate();
+        let sealed_object = seal(&payload, &key);
+        assert_eq!(open(&sealed_object, &key), Ok(payload));
+        assert_eq!(SealedObject::from_bytes(&sealed_object.to_bytes()), Ok(sealed_object.clone()));
+
+        let mut tampered = sealed_object.clone();
+        tampered.payload[0] ^= 1;
+        assert_eq!(open(&tampered, &key), Err(Error::IntegrityCheckFailed));
+    }
+
+    #[test]
+    fn test_sealed_object_rotation() {
+        let payload = b"les sanglots longs des violons de l'automne".to_vec();
+        let old_key = Key::generate();
+        let new_key = Key::generate();
+        let mut sealed_object = seal(&payload, &old_key);
+        let sealed_payload = sealed_object.payload.clone();
+        let payload_buffer = sealed_object.payload.as_ptr();
+        sealed_object.re_encrypt(&ReEncryptionKey::generate(&old_key, &new_key).unwrap()).unwrap();
+        assert_eq!(sealed_object.payload.as_ptr(), payload_buffer);
+        assert_eq!(sealed_object.payload, sealed_payload);
+        assert_eq!(open(&sealed_object, &new_key), Ok(payload));
+        assert_eq!(open(&sealed_object, &old_key), Err(Error::KeyMismatch { expected: new_key.fingerprint(), found: old_key.fingerprint() }));
+    }
+
+    #[test]
+    fn test_sealed_object_associated_data() {
+        let payload = b"les sanglots longs des violons de l'automne".to_vec();
+        let key = Key::generate();
+        let sealed_object = seal(&payload, &key);
+
+        // The header is authenticated, so it cannot be downgraded to a version that authenticates nothing.
+        let mut bytes = sealed_object.to_bytes();
+        bytes[4] = ASSOCIATED_DATA_FORMAT_VERSION - 1;
+        assert_eq!(open(&SealedObject::from_bytes(&bytes).unwrap(), &key), Err(Error::IntegrityCheckFailed));
+
+        // Objects sealed before the header was authenticated still open.
+        let data_key = [7u8; size_of::<Plaintext>()];
+        let nonce = [3u8; NONCE_LENGTH];
+        let legacy = SealedObject {
+            version: ASSOCIATED_DATA_FORMAT_VERSION - 1,
+            data_key: Envelope::new(key.fingerprint(), 0, encrypt(&data_key, &key)),
+            nonce,
+            payload: ChaCha20Poly1305::new(&data_key.into()).encrypt(&nonce.into(), payload.as_slice()).unwrap(),
+        };
+        let legacy = SealedObject::from_bytes(&legacy.to_bytes()).unwrap();
+        assert_eq!(open(&legacy, &key), Ok(payload));
+    }
+}
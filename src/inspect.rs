@@ -0,0 +1,213 @@
+use crate::envelope::{ENVELOPE_MAGIC, MULTI_ENVELOPE_MAGIC};
+use crate::error::Error;
+use crate::hybrid::{SealedObject, SEALED_OBJECT_MAGIC};
+use crate::serialization::Reader;
+use crate::wrapping::WRAPPED_KEY_MAGIC;
+use crate::serialization::{AONT_FORMAT_VERSION, HASH_FORMAT_VERSION};
+use crate::{AontHash, AontKind, Encrypted, Envelope, Fingerprint, MultiEnvelope, WrappedKey};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CiphertextKind {
+    Envelope,
+    MultiEnvelope,
+    SealedObject,
+    WrappedKey,
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemeParameters {
+    pub block_size_bits: u16,
+    pub message_blocks_count: u16,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CiphertextInfo {
+    pub kind: CiphertextKind,
+    pub format_version: Option<u8>,
+    pub scheme_parameters: Option<SchemeParameters>,
+    pub key_fingerprints: Vec<Fingerprint>,
+    pub epoch: Option<u64>,
+    pub aont: Option<AontKind>,
+    pub aont_hash: Option<AontHash>,
+    pub length: usize,
+    pub well_formed: bool,
+    pub error: Option<Error>,
+}
+
+pub fn inspect(bytes: &[u8]) -> CiphertextInfo {
+    let mut info = CiphertextInfo {
+        kind: CiphertextKind::Unknown,
+        format_version: None,
+        scheme_parameters: None,
+        key_fingerprints: Vec::new(),
+        epoch: None,
+        aont: None,
+        aont_hash: None,
+        length: bytes.len(),
+        well_formed: false,
+        error: None,
+    };
+    let mut reader = Reader::new(bytes);
+    if let Err(e) = read_header(&mut reader, &mut info) {
+        info.error = Some(e);
+        return info;
+    }
+    let parsed = match info.kind {
+        CiphertextKind::Envelope => Envelope::from_bytes(bytes).map(drop),
+        CiphertextKind::MultiEnvelope => MultiEnvelope::from_bytes(bytes).map(drop),
+        CiphertextKind::SealedObject => SealedObject::from_bytes(bytes).map(drop),
+        CiphertextKind::WrappedKey => WrappedKey::from_bytes(bytes).map(drop),
+        CiphertextKind::Unknown => Err(Error::InvalidFormat("unexpected magic bytes")),
+    };
+    info.well_formed = parsed.is_ok();
+    info.error = parsed.err();
+    info
+}
+
+fn read_header(reader: &mut Reader, info: &mut CiphertextInfo) -> Result<(), Error> {
+    let magic = reader.read_array::<4>()?;
+    info.kind = match &magic {
+        ENVELOPE_MAGIC => CiphertextKind::Envelope,
+        MULTI_ENVELOPE_MAGIC => CiphertextKind::MultiEnvelope,
+        SEALED_OBJECT_MAGIC => CiphertextKind::SealedObject,
+        WRAPPED_KEY_MAGIC => CiphertextKind::WrappedKey,
+        _ => return Err(Error::InvalidFormat("unexpected magic bytes")),
+    };
+    let version = reader.read_u8()?;
+    info.format_version = Some(version);
+    match info.kind {
+        CiphertextKind::Envelope => read_envelope_fields(reader, version, info)?,
+        CiphertextKind::MultiEnvelope => {
+            read_scheme_parameters(reader, info)?;
+            info.aont = Some(if version >= HASH_FORMAT_VERSION { AontKind::from_byte(reader.read_u8()?)? } else { AontKind::HashXor });
+            let mut aont_hashes = Vec::new();
+            for _ in 0..reader.read_u16()? {
+                info.key_fingerprints.push(reader.read_array()?);
+                aont_hashes.push(if version >= HASH_FORMAT_VERSION { AontHash::from_byte(reader.read_u8()?)? } else { AontHash::Sha3_256 });
+                reader.read_slice(size_of::<Encrypted>())?;
+            }
+            // Recipients may use different hashes, in which case there is no single one to report.
+            info.aont_hash = aont_hashes.first().copied().filter(|first| aont_hashes.iter().all(|aont_hash| aont_hash == first));
+        }
+        CiphertextKind::SealedObject => {
+            let data_key_length = reader.read_u16()? as usize;
+            let mut data_key_reader = Reader::new(reader.read_slice(data_key_length)?);
+            let data_key_version = data_key_reader.read_header(ENVELOPE_MAGIC)?;
+            read_envelope_fields(&mut data_key_reader, data_key_version, info)?;
+        }
+        CiphertextKind::WrappedKey => {
+            info.key_fingerprints.push(reader.read_array()?);
+            info.aont_hash = Some(if version >= HASH_FORMAT_VERSION { AontHash::from_byte(reader.read_u8()?)? } else { AontHash::Sha3_256 });
+        }
+        CiphertextKind::Unknown => unreachable!(),
+    }
+    Ok(())
+}
+
+fn read_envelope_fields(reader: &mut Reader, version: u8, info: &mut CiphertextInfo) -> Result<(), Error> {
+    read_scheme_parameters(reader, info)?;
+    info.aont = Some(if version >= AONT_FORMAT_VERSION { AontKind::from_byte(reader.read_u8()?)? } else { AontKind::HashXor });
+    info.aont_hash = Some(if version >= HASH_FORMAT_VERSION { AontHash::from_byte(reader.read_u8()?)? } else { AontHash::Sha3_256 });
+    info.key_fingerprints.push(reader.read_array()?);
+    info.epoch = Some(reader.read_u64()?);
+    Ok(())
+}
+
+fn read_scheme_parameters(reader: &mut Reader, info: &mut CiphertextInfo) -> Result<(), Error> {
+    info.scheme_parameters = Some(SchemeParameters {
+        block_size_bits: reader.read_u16()?,
+        message_blocks_count: reader.read_u16()?,
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{inspect, CiphertextKind, SchemeParameters};
+    use crate::error::Error;
+    use crate::hybrid::{seal, SEALED_OBJECT_MAGIC};
+    use crate::serialization::FORMAT_VERSION;
+    use crate::{encrypt, encrypt_multi, AontHash, AontKind, Envelope, Key, Scheme, BLOCK_SIZE_BITS, MESSAGE_BLOCKS_COUNT};
+
+    const SCHEME_PARAMETERS: SchemeParameters = SchemeParameters {
+        block_size_bits: BLOCK_SIZE_BITS as u16,
+        message_blocks_count: MESSAGE_BLOCKS_COUNT as u16,
+    };
+
+    #[test]
+    fn test_inspect_envelope() {
+        let key = Key::generate();
+        let bytes = Envelope::new(key.fingerprint(), 7, encrypt(b"les sanglots longs des violons !", &key)).to_bytes();
+        let info = inspect(&bytes);
+        assert_eq!(info.kind, CiphertextKind::Envelope);
+        assert_eq!(info.format_version, Some(FORMAT_VERSION));
+        assert_eq!(info.scheme_parameters, Some(SCHEME_PARAMETERS));
+        assert_eq!(info.key_fingerprints, [key.fingerprint()]);
+        assert_eq!(info.epoch, Some(7));
+        assert_eq!(info.aont, Some(AontKind::HashXor));
+        assert_eq!(info.aont_hash, Some(AontHash::Sha3_256));
+        assert_eq!(info.length, bytes.len());
+        assert!(info.well_formed);
+
+        let info = inspect(&Scheme::new().with_aont(AontKind::Oaep).encrypt_envelope(b"les sanglots longs des violons !", &key.clone().with_aont_hash(AontHash::Blake3)).to_bytes());
+        assert_eq!(info.aont, Some(AontKind::Oaep));
+        assert_eq!(info.aont_hash, Some(AontHash::Blake3));
+        assert!(info.well_formed);
+
+        let info = inspect(&bytes[..bytes.len() - 1]);
+        assert_eq!(info.kind, CiphertextKind::Envelope);
+        assert_eq!(info.epoch, Some(7));
+        assert!(!info.well_formed);
+        assert_eq!(info.error, Some(Error::InvalidFormat("truncated input")));
+    }
+
+    #[test]
+    fn test_inspect_other_kinds() {
+        let key1 = Key::generate();
+        let key2 = Key::generate();
+        let info = inspect(&encrypt_multi(b"les sanglots longs des violons !", &[&key1, &key2]).unwrap().to_bytes());
+        assert_eq!(info.kind, CiphertextKind::MultiEnvelope);
+        assert_eq!(info.key_fingerprints, [key1.fingerprint(), key2.fingerprint()]);
+        assert_eq!(info.aont, Some(AontKind::HashXor));
+        assert_eq!(info.aont_hash, Some(AontHash::Sha3_256));
+        assert!(info.well_formed);
+        let info = inspect(&encrypt_multi(b"les sanglots longs des violons !", &[&key1, &key2.clone().with_aont_hash(AontHash::Blake3)]).unwrap().to_bytes());
+        assert_eq!(info.aont_hash, None);
+        assert!(info.well_formed);
+
+        let info = inspect(&seal(b"payload", &key1).to_bytes());
+        assert_eq!(info.kind, CiphertextKind::SealedObject);
+        assert_eq!(info.scheme_parameters, Some(SCHEME_PARAMETERS));
+        assert_eq!(info.key_fingerprints, [key1.fingerprint()]);
+        assert!(info.well_formed);
+
+        let frames = u16::MAX as usize / 7;
+        let mut nested = Vec::new();
+        for i in 1..=frames {
+            nested.extend_from_slice(SEALED_OBJECT_MAGIC);
+            nested.push(FORMAT_VERSION);
+            nested.extend_from_slice(&((7 * (frames - i)) as u16).to_be_bytes());
+        }
+        let info = inspect(&nested);
+        assert_eq!(info.kind, CiphertextKind::SealedObject);
+        assert!(info.key_fingerprints.is_empty());
+        assert!(!info.well_formed);
+        assert_eq!(info.error, Some(Error::InvalidFormat("unexpected magic bytes")));
+
+        let info = inspect(&key1.wrap(&key2).to_bytes());
+        assert_eq!(info.kind, CiphertextKind::WrappedKey);
+        assert_eq!(info.key_fingerprints, [key2.fingerprint()]);
+        assert_eq!(info.aont_hash, Some(AontHash::Sha3_256));
+        assert!(info.well_formed);
+    }
+
+    #[test]
+    fn test_inspect_garbage() {
+        let info = inspect(b"not a ciphertext");
+        assert_eq!(info.kind, CiphertextKind::Unknown);
+        assert!(!info.well_formed);
+        assert_eq!(inspect(&Key::generate().to_bytes()).kind, CiphertextKind::Unknown);
+        assert_eq!(inspect(b"SP").error, Some(Error::InvalidFormat("truncated input")));
+    }
+}
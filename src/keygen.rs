@@ -1,91 +1,254 @@
-use crate::{BLOCK_SIZE_BITS, BLOCK_SIZE_BYTE, MESSAGE_BLOCKS_COUNT};
-use factorial::Factorial;
-use num_bigint::{BigUint, RandBigInt};
-use num_traits::Zero;
-use rand::Rng;
-use crate::permutations::{find_permute_conversion, generate_permutation, get_permutation_number};
-
-#[derive(Debug, Clone)]
+use crate::error::Error;
+use crate::permutations::Permutation;
+#[cfg(feature = "lehmer")]
+use crate::serialization::{write_biguint, write_lehmer_header};
+use crate::serialization::{write_header, write_permutation, Reader, HASH_FORMAT_VERSION, LEHMER_FORMAT_VERSION};
+use crate::{AontHash, Fingerprint, BLOCK_SIZE_BITS, BLOCK_SIZE_BYTE, MESSAGE_BLOCKS_COUNT};
+use alloc::vec::Vec;
+use rand::{CryptoRng, Rng};
+use sha3::{Digest, Sha3_256};
+
+const KEY_MAGIC: &[u8; 4] = b"SPKY";
+const RE_ENCRYPTION_KEY_MAGIC: &[u8; 4] = b"SPRK";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Key {
-    k1: BigUint,
-    k2: BigUint,
-    k3: BigUint,
+    p1: Permutation<BLOCK_SIZE_BITS>,
+    p2: Permutation<BLOCK_SIZE_BITS>,
+    p3: Permutation<{ MESSAGE_BLOCKS_COUNT + 1 }>,
     kx: [u8; BLOCK_SIZE_BYTE],
+    aont_hash: AontHash,
 }
 
 impl Key {
+    #[cfg(feature = "std")]
     pub fn generate() -> Self {
-        let mut rng = rand::thread_rng();
-        let max_value_block_perm = BigUint::from(BLOCK_SIZE_BITS).factorial();
-        let max_value_block_count = BigUint::from(MESSAGE_BLOCKS_COUNT + 1).factorial();
-        // generate a random number between 0 and max_value
-        let k1 = rng.gen_biguint_range(&BigUint::zero(), &max_value_block_perm);
-        let k2 = rng.gen_biguint_range(&BigUint::zero(), &max_value_block_perm);
-        let k3 = rng.gen_biguint_range(&BigUint::zero(), &max_value_block_count);
+        Self::generate_with_rng(&mut rand::thread_rng())
+    }
+
+    pub fn generate_with_rng<R: CryptoRng + Rng + ?Sized>(rng: &mut R) -> Self {
+        let p1 = Permutation::random(rng);
+        let p2 = Permutation::random(rng);
+        let p3 = Permutation::random(rng);
         let mut kx = [0u8; BLOCK_SIZE_BYTE];
         rng.fill(&mut kx);
-        Self { k1, k2, k3, kx }
+        Self { p1, p2, p3, kx, aont_hash: AontHash::default() }
+    }
+
+    pub fn with_aont_hash(mut self, aont_hash: AontHash) -> Self {
+        self.aont_hash = aont_hash;
+        self
+    }
+
+    pub fn aont_hash(&self) -> AontHash {
+        self.aont_hash
     }
 
-    pub(crate) fn p1(&self) -> [usize; BLOCK_SIZE_BITS] {
-        generate_permutation::<BLOCK_SIZE_BITS>(self.k1.clone())
+    #[cfg(feature = "analysis")]
+    pub(crate) fn from_permutations(p1: Permutation<BLOCK_SIZE_BITS>, p2: Permutation<BLOCK_SIZE_BITS>, p3: Permutation<{ MESSAGE_BLOCKS_COUNT + 1 }>, kx: [u8; BLOCK_SIZE_BYTE]) -> Self {
+        Self { p1, p2, p3, kx, aont_hash: AontHash::default() }
     }
 
-    pub(crate) fn p2(&self) -> [usize; BLOCK_SIZE_BITS] {
-        generate_permutation::<BLOCK_SIZE_BITS>(self.k2.clone())
+    pub(crate) fn p1(&self) -> Permutation<BLOCK_SIZE_BITS> {
+        self.p1
     }
 
-    pub(crate) fn p3(&self) -> [usize; MESSAGE_BLOCKS_COUNT + 1] {
-        generate_permutation::<{ MESSAGE_BLOCKS_COUNT + 1 }>(self.k3.clone())
+    pub(crate) fn p2(&self) -> Permutation<BLOCK_SIZE_BITS> {
+        self.p2
+    }
+
+    pub(crate) fn p3(&self) -> Permutation<{ MESSAGE_BLOCKS_COUNT + 1 }> {
+        self.p3
     }
 
     pub(crate) fn kx(&self) -> [u8; BLOCK_SIZE_BYTE] {
         self.kx
     }
+
+    pub fn fingerprint(&self) -> Fingerprint {
+        // Only the key material is hashed, so that the fingerprint survives format changes and AONT hash choices.
+        let mut material = Vec::new();
+        write_permutation(&mut material, self.p1.as_array());
+        write_permutation(&mut material, self.p2.as_array());
+        write_permutation(&mut material, self.p3.as_array());
+        material.extend_from_slice(&self.kx);
+        let mut hasher = Sha3_256::new();
+        Digest::update(&mut hasher, material);
+        hasher.finalize()[..8].try_into().unwrap()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut output = Vec::new();
+        write_header(&mut output, KEY_MAGIC);
+        output.push(self.aont_hash.to_byte());
+        write_permutation(&mut output, self.p1.as_array());
+        write_permutation(&mut output, self.p2.as_array());
+        write_permutation(&mut output, self.p3.as_array());
+        output.extend_from_slice(&self.kx);
+        output
+    }
+
+    #[cfg(feature = "lehmer")]
+    pub fn to_lehmer_bytes(&self) -> Result<Vec<u8>, Error> {
+        if self.aont_hash != AontHash::Sha3_256 {
+            return Err(Error::InvalidFormat("Lehmer-encoded keys cannot record an AONT hash other than SHA3-256"));
+        }
+        let mut output = Vec::new();
+        write_lehmer_header(&mut output, KEY_MAGIC);
+        write_biguint(&mut output, &self.p1.to_lehmer());
+        write_biguint(&mut output, &self.p2.to_lehmer());
+        write_biguint(&mut output, &self.p3.to_lehmer());
+        output.extend_from_slice(&self.kx);
+        Ok(output)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader::new(bytes);
+        let version = reader.read_header(KEY_MAGIC)?;
+        let aont_hash = if version >= HASH_FORMAT_VERSION { AontHash::from_byte(reader.read_u8()?)? } else { AontHash::Sha3_256 };
+        let (p1, p2, p3) = match version {
+            LEHMER_FORMAT_VERSION => (read_lehmer_permutation(&mut reader)?, read_lehmer_permutation(&mut reader)?, read_lehmer_permutation(&mut reader)?),
+            _ => (Permutation::new(reader.read_permutation()?)?, Permutation::new(reader.read_permutation()?)?, Permutation::new(reader.read_permutation()?)?),
+        };
+        let kx = reader.read_array()?;
+        reader.finish()?;
+        Ok(Self { p1, p2, p3, kx, aont_hash })
+    }
+}
+
+fn read_lehmer_permutation<const ELEMENTS_COUNT: usize>(reader: &mut Reader) -> Result<Permutation<ELEMENTS_COUNT>, Error> {
+    Permutation::from_lehmer_bytes(reader.read_biguint_bytes()?)
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ReEncryptionKey {
-    ck1: BigUint,
-    ck3: BigUint,
+    source_fingerprint: Fingerprint,
+    target_fingerprint: Fingerprint,
+    cp1: Permutation<BLOCK_SIZE_BITS>,
+    cp3: Permutation<{ MESSAGE_BLOCKS_COUNT + 1 }>,
     old_kx: [u8; BLOCK_SIZE_BYTE],
     new_kx: [u8; BLOCK_SIZE_BYTE],
-    old_k2: BigUint,
-    new_k2: BigUint,
+    old_p2: Permutation<BLOCK_SIZE_BITS>,
+    new_p2: Permutation<BLOCK_SIZE_BITS>,
 }
 
 impl ReEncryptionKey {
-    pub fn generate(old_key: &Key, new_key: &Key) -> Self {
-        let p1_old = old_key.p1();
-        let p3_old = old_key.p3();
-        let p1_new = new_key.p1();
-        let p3_new = new_key.p3();
-        let ck1 = get_permutation_number(find_permute_conversion(&p1_old, &p1_new));
-        let ck3 = get_permutation_number(find_permute_conversion(&p3_old, &p3_new));
+    // The proxy cannot redo the AONT, so a ciphertext keeps the hash of the key it was first encrypted under.
+    pub fn generate(old_key: &Key, new_key: &Key) -> Result<Self, Error> {
+        if old_key.aont_hash != new_key.aont_hash {
+            return Err(Error::AontHashMismatch { expected: old_key.aont_hash, found: new_key.aont_hash });
+        }
+        Ok(Self {
+            source_fingerprint: old_key.fingerprint(),
+            target_fingerprint: new_key.fingerprint(),
+            cp1: old_key.p1.inverse().compose(&new_key.p1),
+            cp3: old_key.p3.inverse().compose(&new_key.p3),
+            old_kx: old_key.kx,
+            new_kx: new_key.kx,
+            old_p2: old_key.p2,
+            new_p2: new_key.p2,
+        })
+    }
 
+    pub fn compose(first: &ReEncryptionKey, second: &ReEncryptionKey) -> Result<Self, Error> {
+        if first.target_fingerprint != second.source_fingerprint {
+            return Err(Error::KeyMismatch { expected: first.target_fingerprint, found: second.source_fingerprint });
+        }
+        Ok(Self {
+            source_fingerprint: first.source_fingerprint,
+            target_fingerprint: second.target_fingerprint,
+            cp1: first.cp1.compose(&second.cp1),
+            cp3: first.cp3.compose(&second.cp3),
+            old_kx: first.old_kx,
+            new_kx: second.new_kx,
+            old_p2: first.old_p2,
+            new_p2: second.new_p2,
+        })
+    }
+
+    pub fn inverse(&self) -> Self {
         Self {
-            ck1,
-            ck3,
-            old_kx: old_key.kx(),
-            new_kx: new_key.kx(),
-            old_k2: old_key.k2.clone(),
-            new_k2: new_key.k2.clone(),
+            source_fingerprint: self.target_fingerprint,
+            target_fingerprint: self.source_fingerprint,
+            cp1: self.cp1.inverse(),
+            cp3: self.cp3.inverse(),
+            old_kx: self.new_kx,
+            new_kx: self.old_kx,
+            old_p2: self.new_p2,
+            new_p2: self.old_p2,
         }
     }
 
-    pub(crate) fn old_p2(&self) -> [usize; BLOCK_SIZE_BITS] {
-        generate_permutation::<BLOCK_SIZE_BITS>(self.old_k2.clone())
+    pub fn source_fingerprint(&self) -> Fingerprint {
+        self.source_fingerprint
+    }
+
+    pub fn target_fingerprint(&self) -> Fingerprint {
+        self.target_fingerprint
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut output = Vec::new();
+        write_header(&mut output, RE_ENCRYPTION_KEY_MAGIC);
+        output.extend_from_slice(&self.source_fingerprint);
+        output.extend_from_slice(&self.target_fingerprint);
+        write_permutation(&mut output, self.cp1.as_array());
+        write_permutation(&mut output, self.cp3.as_array());
+        output.extend_from_slice(&self.old_kx);
+        output.extend_from_slice(&self.new_kx);
+        write_permutation(&mut output, self.old_p2.as_array());
+        write_permutation(&mut output, self.new_p2.as_array());
+        output
+    }
+
+    #[cfg(feature = "lehmer")]
+    pub fn to_lehmer_bytes(&self) -> Vec<u8> {
+        let mut output = Vec::new();
+        write_lehmer_header(&mut output, RE_ENCRYPTION_KEY_MAGIC);
+        output.extend_from_slice(&self.source_fingerprint);
+        output.extend_from_slice(&self.target_fingerprint);
+        write_biguint(&mut output, &self.cp1.to_lehmer());
+        write_biguint(&mut output, &self.cp3.to_lehmer());
+        output.extend_from_slice(&self.old_kx);
+        output.extend_from_slice(&self.new_kx);
+        write_biguint(&mut output, &self.old_p2.to_lehmer());
+        write_biguint(&mut output, &self.new_p2.to_lehmer());
+        output
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader::new(bytes);
+        let version = reader.read_header(RE_ENCRYPTION_KEY_MAGIC)?;
+        let source_fingerprint = reader.read_array()?;
+        let target_fingerprint = reader.read_array()?;
+        let (cp1, cp3) = match version {
+            LEHMER_FORMAT_VERSION => (read_lehmer_permutation(&mut reader)?, read_lehmer_permutation(&mut reader)?),
+            _ => (Permutation::new(reader.read_permutation()?)?, Permutation::new(reader.read_permutation()?)?),
+        };
+        let old_kx = reader.read_array()?;
+        let new_kx = reader.read_array()?;
+        let (old_p2, new_p2) = match version {
+            LEHMER_FORMAT_VERSION => (read_lehmer_permutation(&mut reader)?, read_lehmer_permutation(&mut reader)?),
+            _ => (Permutation::new(reader.read_permutation()?)?, Permutation::new(reader.read_permutation()?)?),
+        };
+        reader.finish()?;
+        Ok(Self { source_fingerprint, target_fingerprint, cp1, cp3, old_kx, new_kx, old_p2, new_p2 })
+    }
+
+    pub(crate) fn old_p2(&self) -> Permutation<BLOCK_SIZE_BITS> {
+        self.old_p2
     }
 
-    pub(crate) fn new_p2(&self) -> [usize; BLOCK_SIZE_BITS] {
-        generate_permutation::<BLOCK_SIZE_BITS>(self.new_k2.clone())
+    pub(crate) fn new_p2(&self) -> Permutation<BLOCK_SIZE_BITS> {
+        self.new_p2
     }
 
-    pub(crate) fn cp1(&self) -> [usize; BLOCK_SIZE_BITS] {
-        generate_permutation(self.ck1.clone())
+    pub(crate) fn cp1(&self) -> Permutation<BLOCK_SIZE_BITS> {
+        self.cp1
     }
 
-    pub(crate) fn cp3(&self) -> [usize; MESSAGE_BLOCKS_COUNT + 1] {
-        generate_permutation(self.ck3.clone())
+    pub(crate) fn cp3(&self) -> Permutation<{ MESSAGE_BLOCKS_COUNT + 1 }> {
+        self.cp3
     }
 
     pub(crate) fn old_kx(&self) -> [u8; BLOCK_SIZE_BYTE] {
@@ -95,4 +258,149 @@ impl ReEncryptionKey {
     pub(crate) fn new_kx(&self) -> [u8; BLOCK_SIZE_BYTE] {
         self.new_kx
     }
-}
\ No newline at end of file
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::error::Error;
+    use crate::{AontHash, Key, ReEncryptionKey, BLOCK_SIZE_BITS, BLOCK_SIZE_BYTE, MESSAGE_BLOCKS_COUNT};
+    use crate::permutations::Permutation;
+
+    #[test]
+    fn test_key_serialization() {
+        let key = Key::generate();
+        let bytes = key.to_bytes();
+        assert_eq!(Key::from_bytes(&bytes), Ok(key.clone()));
+        assert_eq!(Key::from_bytes(&bytes[1..]), Err(Error::InvalidFormat("unexpected magic bytes")));
+        assert_ne!(key.fingerprint(), Key::generate().fingerprint());
+    }
+
+    #[test]
+    fn test_key_aont_hash() {
+        let key = Key::generate();
+        assert_eq!(key.aont_hash(), AontHash::Sha3_256);
+        let blake3_key = key.clone().with_aont_hash(AontHash::Blake3);
+        assert_eq!(Key::from_bytes(&blake3_key.to_bytes()), Ok(blake3_key.clone()));
+        assert_eq!(blake3_key.fingerprint(), key.fingerprint());
+
+        // Keys recorded their AONT hash from version 4, older ones used SHA3-256.
+        let mut bytes = key.to_bytes();
+        bytes[4] = 3;
+        bytes.remove(5);
+        assert_eq!(Key::from_bytes(&bytes), Ok(key));
+        let mut bytes = blake3_key.to_bytes();
+        bytes[5] = 4;
+        assert_eq!(Key::from_bytes(&bytes), Err(Error::InvalidFormat("unknown AONT hash")));
+    }
+
+    #[test]
+    fn test_re_encryption_key_serialization() {
+        let old_key = Key::generate();
+        let new_key = Key::generate();
+        let re_encryption_key = ReEncryptionKey::generate(&old_key, &new_key).unwrap();
+        assert_eq!(re_encryption_key.source_fingerprint(), old_key.fingerprint());
+        assert_eq!(re_encryption_key.target_fingerprint(), new_key.fingerprint());
+        assert_eq!(ReEncryptionKey::from_bytes(&re_encryption_key.to_bytes()), Ok(re_encryption_key.clone()));
+        assert_eq!(ReEncryptionKey::from_bytes(&old_key.to_bytes()), Err(Error::InvalidFormat("unexpected magic bytes")));
+    }
+
+    #[test]
+    fn test_permutation_validation() {
+        // Keys hold p1, p2 and p3 after their 5-byte header and AONT hash byte, one byte per index.
+        let bytes = Key::generate().to_bytes();
+        let mut duplicate_index = bytes.clone();
+        duplicate_index[7] = duplicate_index[6];
+        assert_eq!(Key::from_bytes(&duplicate_index), Err(Error::InvalidFormat("not a bijection")));
+        let mut index_out_of_range = bytes.clone();
+        index_out_of_range[6 + 2 * BLOCK_SIZE_BITS] = MESSAGE_BLOCKS_COUNT as u8 + 1;
+        assert_eq!(Key::from_bytes(&index_out_of_range), Err(Error::InvalidFormat("not a bijection")));
+
+        // Re-encryption keys hold cp1 and cp3 after their header and both fingerprints.
+        let bytes = ReEncryptionKey::generate(&Key::generate(), &Key::generate()).unwrap().to_bytes();
+        let mut duplicate_index = bytes.clone();
+        duplicate_index[22] = duplicate_index[21];
+        assert_eq!(ReEncryptionKey::from_bytes(&duplicate_index), Err(Error::InvalidFormat("not a bijection")));
+        let mut duplicate_index = bytes.clone();
+        duplicate_index[22 + BLOCK_SIZE_BITS] = duplicate_index[21 + BLOCK_SIZE_BITS];
+        assert_eq!(ReEncryptionKey::from_bytes(&duplicate_index), Err(Error::InvalidFormat("not a bijection")));
+        let mut duplicate_index = bytes.clone();
+        *duplicate_index.last_mut().unwrap() = duplicate_index[duplicate_index.len() - 2];
+        assert_eq!(ReEncryptionKey::from_bytes(&duplicate_index), Err(Error::InvalidFormat("not a bijection")));
+    }
+
+    #[test]
+    fn test_lehmer_key_loading() {
+        // A version 1 key whose Lehmer codes are all 0, i.e. identity permutations.
+        let mut bytes = b"SPKY\x01\x00\x01\x00\x00\x01\x00\x00\x01\x00".to_vec();
+        bytes.extend_from_slice(&[0; BLOCK_SIZE_BYTE]);
+        let key = Key::from_bytes(&bytes).unwrap();
+        assert_eq!(key.p1, Permutation::identity());
+        assert_eq!(key.p3, Permutation::identity());
+    }
+
+    #[test]
+    fn test_fingerprint_vector() {
+        // Identity permutations and a zero kx, read from version 1 and written back in the current format.
+        let mut bytes = b"SPKY\x01\x00\x01\x00\x00\x01\x00\x00\x01\x00".to_vec();
+        bytes.extend_from_slice(&[0; BLOCK_SIZE_BYTE]);
+        let key = Key::from_bytes(&bytes).unwrap();
+        assert_eq!(key.fingerprint(), [0xac, 0x34, 0x34, 0xbb, 0x0d, 0xf2, 0x9d, 0x56]);
+        assert_eq!(Key::from_bytes(&key.to_bytes()).unwrap().fingerprint(), key.fingerprint());
+        assert_eq!(key.clone().with_aont_hash(AontHash::Shake128).fingerprint(), key.fingerprint());
+    }
+
+    #[cfg(feature = "lehmer")]
+    #[test]
+    fn test_lehmer_code_validation() {
+        use crate::serialization::write_biguint;
+        use num_bigint::BigUint;
+
+        // A Lehmer code of N! or more would wrap around into the top digit and yield a non-invertible array.
+        let factorial: BigUint = (1..=BLOCK_SIZE_BITS).map(BigUint::from).product();
+        let mut bytes = b"SPKY\x01".to_vec();
+        write_biguint(&mut bytes, &factorial);
+        write_biguint(&mut bytes, &BigUint::from(0usize));
+        write_biguint(&mut bytes, &BigUint::from(0usize));
+        bytes.extend_from_slice(&[0; BLOCK_SIZE_BYTE]);
+        assert_eq!(Key::from_bytes(&bytes), Err(Error::InvalidFormat("Lehmer code out of range")));
+    }
+
+    #[test]
+    fn test_proxy_knowledge() {
+        let old_key = Key::generate();
+        let new_key = Key::generate();
+        let re_encryption_key = ReEncryptionKey::generate(&old_key, &new_key).unwrap();
+
+        // Re-encryption undoes and redoes the chaining and the first block's mask, so the proxy learns p2 and kx.
+        assert_eq!(re_encryption_key.old_p2(), old_key.p2());
+        assert_eq!(re_encryption_key.new_p2(), new_key.p2());
+        assert_eq!(re_encryption_key.old_kx(), old_key.kx);
+        assert_eq!(re_encryption_key.new_kx(), new_key.kx);
+
+        // p1 and p3 are only known up to a common permutation: another pair of keys yields the same conversions.
+        let other_old_key = Key::generate();
+        let other_new_key = Key {
+            p1: other_old_key.p1().compose(&re_encryption_key.cp1()),
+            p2: new_key.p2,
+            p3: other_old_key.p3().compose(&re_encryption_key.cp3()),
+            kx: new_key.kx,
+            aont_hash: new_key.aont_hash,
+        };
+        let other_re_encryption_key = ReEncryptionKey::generate(&other_old_key, &other_new_key).unwrap();
+        assert_ne!(other_new_key.p1, new_key.p1);
+        assert_eq!(other_re_encryption_key.cp1, re_encryption_key.cp1);
+        assert_eq!(other_re_encryption_key.cp3, re_encryption_key.cp3);
+    }
+
+    #[cfg(feature = "lehmer")]
+    #[test]
+    fn test_lehmer_serialization() {
+        let old_key = Key::generate();
+        let new_key = Key::generate();
+        let re_encryption_key = ReEncryptionKey::generate(&old_key, &new_key).unwrap();
+        assert!(old_key.to_lehmer_bytes().unwrap().len() < old_key.to_bytes().len());
+        assert_eq!(Key::from_bytes(&old_key.to_lehmer_bytes().unwrap()), Ok(old_key.clone()));
+        assert_eq!(ReEncryptionKey::from_bytes(&re_encryption_key.to_lehmer_bytes()), Ok(re_encryption_key));
+        assert!(old_key.with_aont_hash(AontHash::Blake3).to_lehmer_bytes().is_err());
+    }
+}
@@ -0,0 +1,139 @@
+use crate::error::Error;
+use crate::{encrypt, Envelope, Key, Plaintext, ReEncryptionKey};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpdateToken {
+    from_epoch: u64,
+    to_epoch: u64,
+    re_encryption_key: ReEncryptionKey,
+}
+
+impl UpdateToken {
+    pub(crate) fn new(from_epoch: u64, to_epoch: u64, re_encryption_key: ReEncryptionKey) -> Self {
+        Self { from_epoch, to_epoch, re_encryption_key }
+    }
+
+    pub fn from_epoch(&self) -> u64 {
+        self.from_epoch
+    }
+
+    pub fn to_epoch(&self) -> u64 {
+        self.to_epoch
+    }
+
+    pub fn re_encryption_key(&self) -> &ReEncryptionKey {
+        &self.re_encryption_key
+    }
+
+    pub fn apply(&self, envelope: &Envelope) -> Result<Envelope, Error> {
+        if envelope.epoch() != self.from_epoch {
+            return Err(Error::EpochMismatch { expected: self.from_epoch, found: envelope.epoch() });
+        }
+        let re_encrypted = envelope.re_encrypt(&self.re_encryption_key)?;
+        Ok(Envelope::new(re_encrypted.key_fingerprint(), self.to_epoch, *re_encrypted.ciphertext()).with_aont(re_encrypted.aont()).with_aont_hash(re_encrypted.aont_hash()))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct KeyRing {
+    keys: Vec<Key>,
+    tokens: Vec<ReEncryptionKey>,
+}
+
+impl KeyRing {
+    pub fn new() -> Self {
+        Self {
+            keys: vec![Key::generate()],
+            tokens: Vec::new(),
+        }
+    }
+
+    pub fn current_epoch(&self) -> u64 {
+        (self.keys.len() - 1) as u64
+    }
+
+    pub fn current_key(&self) -> &Key {
+        self.keys.last().unwrap()
+    }
+
+    pub fn key(&self, epoch: u64) -> Option<&Key> {
+        self.keys.get(usize::try_from(epoch).ok()?)
+    }
+
+    pub fn rotate(&mut self) -> UpdateToken {
+        let new_key = Key::generate().with_aont_hash(self.current_key().aont_hash());
+        let re_encryption_key = ReEncryptionKey::generate(self.current_key(), &new_key).unwrap();
+        self.keys.push(new_key);
+        self.tokens.push(re_encryption_key.clone());
+        UpdateToken::new(self.current_epoch() - 1, self.current_epoch(), re_encryption_key)
+    }
+
+    pub fn update_token(&self, from_epoch: u64) -> Result<UpdateToken, Error> {
+        let old_key = self.key(from_epoch).ok_or(Error::UnknownEpoch(from_epoch))?;
+        let mut tokens = self.tokens[from_epoch as usize..].iter();
+        let re_encryption_key = match tokens.next() {
+            Some(first) => tokens.try_fold(first.clone(), |composed, token| ReEncryptionKey::compose(&composed, token))?,
+            None => ReEncryptionKey::generate(old_key, old_key)?,
+        };
+        Ok(UpdateToken::new(from_epoch, self.current_epoch(), re_encryption_key))
+    }
+
+    pub fn encrypt(&self, message: &Plaintext) -> Envelope {
+        Envelope::new(self.current_key().fingerprint(), self.current_epoch(), encrypt(message, self.current_key())).with_aont_hash(self.current_key().aont_hash())
+    }
+
+    pub fn decrypt(&self, envelope: &Envelope) -> Result<Plaintext, Error> {
+        let key = self.key(envelope.epoch()).ok_or(Error::UnknownEpoch(envelope.epoch()))?;
+        if key.fingerprint() != envelope.key_fingerprint() {
+            return Err(Error::KeyMismatch { expected: key.fingerprint(), found: envelope.key_fingerprint() });
+        }
+        envelope.decrypt(key)
+    }
+}
+
+impl Default for KeyRing {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::Error;
+    use crate::KeyRing;
+
+    #[test]
+    fn test_rotation() {
+        let message = b"les sanglots longs des violons !";
+        let mut key_ring = KeyRing::new();
+        let envelope = key_ring.encrypt(message);
+        assert_eq!(envelope.epoch(), 0);
+
+        let token = key_ring.rotate();
+        assert_eq!((token.from_epoch(), token.to_epoch()), (0, 1));
+        let updated = token.apply(&envelope).unwrap();
+        assert_eq!(updated.epoch(), 1);
+        assert_eq!(message, &key_ring.decrypt(&updated).unwrap());
+        assert_eq!(token.apply(&updated), Err(Error::EpochMismatch { expected: 0, found: 1 }));
+    }
+
+    #[test]
+    fn test_update_token_composition() {
+        let message = b"les sanglots longs des violons !";
+        let mut key_ring = KeyRing::new();
+        let envelope = key_ring.encrypt(message);
+        let first_token = key_ring.rotate();
+        let second_token = key_ring.rotate();
+        let third_token = key_ring.rotate();
+        assert_eq!(key_ring.current_epoch(), 3);
+
+        let step_by_step = third_token.apply(&second_token.apply(&first_token.apply(&envelope).unwrap()).unwrap()).unwrap();
+        let composed = key_ring.update_token(0).unwrap().apply(&envelope).unwrap();
+        assert_eq!(step_by_step, composed);
+        assert_eq!(message, &key_ring.decrypt(&composed).unwrap());
+
+        let current = key_ring.encrypt(message);
+        assert_eq!(key_ring.update_token(3).unwrap().apply(&current).unwrap(), current);
+        assert_eq!(key_ring.update_token(4), Err(Error::UnknownEpoch(4)));
+    }
+}
@@ -0,0 +1,365 @@
+use crate::error::Error;
+use crate::serialization::{write_header, Reader};
+use crate::{Key, ReEncryptionKey};
+use argon2::Argon2;
+use chacha20poly1305::aead::Aead;
+use chacha20poly1305::{ChaCha20Poly1305, KeyInit};
+use rand::Rng;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub const KEY_EXTENSION: &str = "key";
+pub const RE_ENCRYPTION_KEY_EXTENSION: &str = "rekey";
+const PASSPHRASE_WRAPPED_MAGIC: &[u8; 4] = b"SPPW";
+const SALT_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 12;
+
+pub trait KeyStore {
+    fn get_key(&self, id: &str) -> Result<Option<Key>, Error>;
+    fn put_key(&mut self, id: &str, key: &Key) -> Result<(), Error>;
+    fn delete_key(&mut self, id: &str) -> Result<bool, Error>;
+    fn list_keys(&self) -> Result<Vec<String>, Error>;
+
+    fn get_re_encryption_key(&self, id: &str) -> Result<Option<ReEncryptionKey>, Error>;
+    fn put_re_encryption_key(&mut self, id: &str, re_encryption_key: &ReEncryptionKey) -> Result<(), Error>;
+    fn delete_re_encryption_key(&mut self, id: &str) -> Result<bool, Error>;
+    fn list_re_encryption_keys(&self) -> Result<Vec<String>, Error>;
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MemoryKeyStore {
+    keys: HashMap<String, Key>,
+    re_encryption_keys: HashMap<String, ReEncryptionKey>,
+}
+
+impl MemoryKeyStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl KeyStore for MemoryKeyStore {
+    fn get_key(&self, id: &str) -> Result<Option<Key>, Error> {
+        Ok(self.keys.get(id).cloned())
+    }
+
+    fn put_key(&mut self, id: &str, key: &Key) -> Result<(), Error> {
+        self.keys.insert(id.to_string(), key.clone());
+        Ok(())
+    }
+
+    fn delete_key(&mut self, id: &str) -> Result<bool, Error> {
+        Ok(self.keys.remove(id).is_some())
+    }
+
+    fn list_keys(&self) -> Result<Vec<String>, Error> {
+        let mut ids: Vec<String> = self.keys.keys().cloned().collect();
+        ids.sort();
+        Ok(ids)
+    }
+
+    fn get_re_encryption_key(&self, id: &str) -> Result<Option<ReEncryptionKey>, Error> {
+        Ok(self.re_encryption_keys.get(id).cloned())
+    }
+
+    fn put_re_encryption_key(&mut self, id: &str, re_encryption_key: &ReEncryptionKey) -> Result<(), Error> {
+        self.re_encryption_keys.insert(id.to_string(), re_encryption_key.clone());
+        Ok(())
+    }
+
+    fn delete_re_encryption_key(&mut self, id: &str) -> Result<bool, Error> {
+        Ok(self.re_encryption_keys.remove(id).is_some())
+    }
+
+    fn list_re_encryption_keys(&self) -> Result<Vec<String>, Error> {
+        let mut ids: Vec<String> = self.re_encryption_keys.keys().cloned().collect();
+        ids.sort();
+        Ok(ids)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FileKeyStore {
+    dir: PathBuf,
+    passphrase: Option<String>,
+}
+
+impl FileKeyStore {
+    // A directory the store creates is restricted to its owner. An existing one is left as it is, since it may belong
+    // to someone else (e.g. a proxy only reading it): its key files are still only readable by their owner, so its
+    // group or others being able to list it only deserves a warning.
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<Self, Error> {
+        let dir = dir.as_ref().to_path_buf();
+        if dir.is_dir() {
+            #[cfg(unix)]
+            if std::os::unix::fs::PermissionsExt::mode(&fs::metadata(&dir)?.permissions()) & 0o077 != 0 {
+                eprintln!("warning: key store {} is accessible by its group or others", dir.display());
+            }
+        } else {
+            let mut builder = fs::DirBuilder::new();
+            builder.recursive(true);
+            #[cfg(unix)]
+            std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+            builder.create(&dir)?;
+            #[cfg(unix)]
+            fs::set_permissions(&dir, std::os::unix::fs::PermissionsExt::from_mode(0o700))?;
+        }
+        Ok(Self { dir, passphrase: None })
+    }
+
+    pub fn with_passphrase(mut self, passphrase: &str) -> Self {
+        self.passphrase = Some(passphrase.to_string());
+        self
+    }
+
+    fn path(&self, id: &str, extension: &str) -> Result<PathBuf, Error> {
+        if id.is_empty() || id.starts_with('.') || id.contains(['/', '\\']) {
+            return Err(Error::InvalidId(id.to_string()));
+        }
+        Ok(self.dir.join(format!("{}.{}", id, extension)))
+    }
+
+    fn read(&self, id: &str, extension: &str) -> Result<Option<Vec<u8>>, Error> {
+        let bytes = match fs::read(self.path(id, extension)?) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        // With a passphrase, plaintext files are refused: otherwise anyone able to write to the directory could plant
+        // a key of their own.
+        match (&self.passphrase, bytes.starts_with(PASSPHRASE_WRAPPED_MAGIC)) {
+            (Some(passphrase), true) => unwrap_with_passphrase(&bytes, passphrase).map(Some),
+            (Some(_), false) => Err(Error::InvalidFormat("key file is not passphrase-wrapped")),
+            (None, true) => Err(Error::PassphraseRequired),
+            (None, false) => Ok(Some(bytes)),
+        }
+    }
+
+    fn write(&self, id: &str, extension: &str, bytes: &[u8]) -> Result<(), Error> {
+        let path = self.path(id, extension)?;
+        let bytes = match &self.passphrase {
+            Some(passphrase) => wrap_with_passphrase(bytes, passphrase),
+            None => bytes.to_vec(),
+        };
+        // Written to a temporary file next to the final one, then renamed over it, so that a crash leaves either the
+        // old or the new file in place. Temporary names start with a dot, which ids cannot.
+        let temporary_path = self.dir.join(format!(".{}.{}.{:016x}.tmp", id, extension, rand::thread_rng().gen::<u64>()));
+        let result = write_synced(&temporary_path, &bytes).and_then(|()| fs::rename(&temporary_path, &path));
+        if result.is_err() {
+            let _ = fs::remove_file(&temporary_path);
+        }
+        result?;
+        #[cfg(unix)]
+        fs::File::open(&self.dir)?.sync_all()?;
+        Ok(())
+    }
+
+    fn delete(&self, id: &str, extension: &str) -> Result<bool, Error> {
+        match fs::remove_file(self.path(id, extension)?) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn list(&self, extension: &str) -> Result<Vec<String>, Error> {
+        let mut ids = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|e| e == extension) {
+                ids.push(path.file_stem().unwrap().to_string_lossy().into_owned());
+            }
+        }
+        ids.sort();
+        Ok(ids)
+    }
+}
+
+impl KeyStore for FileKeyStore {
+    fn get_key(&self, id: &str) -> Result<Option<Key>, Error> {
+        self.read(id, KEY_EXTENSION)?.map(|bytes| Key::from_bytes(&bytes)).transpose()
+    }
+
+    fn put_key(&mut self, id: &str, key: &Key) -> Result<(), Error> {
+        self.write(id, KEY_EXTENSION, &key.to_bytes())
+    }
+
+    fn delete_key(&mut self, id: &str) -> Result<bool, Error> {
+        self.delete(id, KEY_EXTENSION)
+    }
+
+    fn list_keys(&self) -> Result<Vec<String>, Error> {
+        self.list(KEY_EXTENSION)
+    }
+
+    fn get_re_encryption_key(&self, id: &str) -> Result<Option<ReEncryptionKey>, Error> {
+        self.read(id, RE_ENCRYPTION_KEY_EXTENSION)?.map(|bytes| ReEncryptionKey::from_bytes(&bytes)).transpose()
+    }
+
+    fn put_re_encryption_key(&mut self, id: &str, re_encryption_key: &ReEncryptionKey) -> Result<(), Error> {
+        self.write(id, RE_ENCRYPTION_KEY_EXTENSION, &re_encryption_key.to_bytes())
+    }
+
+    fn delete_re_encryption_key(&mut self, id: &str) -> Result<bool, Error> {
+        self.delete(id, RE_ENCRYPTION_KEY_EXTENSION)
+    }
+
+    fn list_re_encryption_keys(&self) -> Result<Vec<String>, Error> {
+        self.list(RE_ENCRYPTION_KEY_EXTENSION)
+    }
+}
+
+fn write_synced(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path)?;
+    #[cfg(unix)]
+    file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+    io::Write::write_all(&mut file, bytes)?;
+    file.sync_all()
+}
+
+fn passphrase_cipher(passphrase: &str, salt: &[u8; SALT_LENGTH]) -> ChaCha20Poly1305 {
+    let mut wrapping_key = [0u8; 32];
+    Argon2::default().hash_password_into(passphrase.as_bytes(), salt, &mut wrapping_key).unwrap();
+    ChaCha20Poly1305::new(&wrapping_key.into())
+}
+
+fn wrap_with_passphrase(bytes: &[u8], passphrase: &str) -> Vec<u8> {
+    let mut rng = rand::thread_rng();
+    let mut salt = [0u8; SALT_LENGTH];
+    let mut nonce = [0u8; NONCE_LENGTH];
+    rng.fill(&mut salt);
+    rng.fill(&mut nonce);
+    let ciphertext = passphrase_cipher(passphrase, &salt).encrypt(&nonce.into(), bytes).unwrap();
+    let mut output = Vec::new();
+    write_header(&mut output, PASSPHRASE_WRAPPED_MAGIC);
+    output.extend_from_slice(&salt);
+    output.extend_from_slice(&nonce);
+    output.extend_from_slice(&ciphertext);
+    output
+}
+
+fn unwrap_with_passphrase(bytes: &[u8], passphrase: &str) -> Result<Vec<u8>, Error> {
+    let mut reader = Reader::new(bytes);
+    reader.read_header(PASSPHRASE_WRAPPED_MAGIC)?;
+    let salt = reader.read_array::<SALT_LENGTH>()?;
+    let nonce = reader.read_array::<NONCE_LENGTH>()?;
+    let ciphertext = reader.read_remaining();
+    passphrase_cipher(passphrase, &salt).decrypt(&nonce.into(), ciphertext).map_err(|_| Error::IntegrityCheckFailed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FileKeyStore, KeyStore, MemoryKeyStore};
+    use crate::error::Error;
+    use crate::{Key, ReEncryptionKey};
+
+    fn exercise(store: &mut dyn KeyStore) {
+        let key1 = Key::generate();
+        let key2 = Key::generate();
+        let re_encryption_key = ReEncryptionKey::generate(&key1, &key2).unwrap();
+        store.put_key("alice", &key1).unwrap();
+        store.put_key("bob", &key2).unwrap();
+        store.put_re_encryption_key("alice-to-bob", &re_encryption_key).unwrap();
+
+        assert_eq!(store.get_key("alice").unwrap(), Some(key1));
+        assert_eq!(store.get_key("carol").unwrap(), None);
+        assert_eq!(store.list_keys().unwrap(), ["alice", "bob"]);
+        assert_eq!(store.get_re_encryption_key("alice-to-bob").unwrap(), Some(re_encryption_key));
+        assert_eq!(store.list_re_encryption_keys().unwrap(), ["alice-to-bob"]);
+
+        assert!(store.delete_key("alice").unwrap());
+        assert!(!store.delete_key("alice").unwrap());
+        assert_eq!(store.list_keys().unwrap(), ["bob"]);
+        assert!(store.delete_re_encryption_key("alice-to-bob").unwrap());
+        assert_eq!(store.get_re_encryption_key("alice-to-bob").unwrap(), None);
+    }
+
+    #[test]
+    fn test_memory_key_store() {
+        exercise(&mut MemoryKeyStore::new());
+    }
+
+    #[test]
+    fn test_file_key_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = FileKeyStore::open(dir.path().join("keys")).unwrap();
+        exercise(&mut store);
+        assert_eq!(store.put_key("../escape", &Key::generate()), Err(Error::InvalidId("../escape".to_string())));
+
+        let mut corrupted = Key::generate().to_bytes();
+        corrupted[7] = corrupted[6];
+        std::fs::write(dir.path().join("keys/mallory.key"), corrupted).unwrap();
+        assert_eq!(store.get_key("mallory"), Err(Error::InvalidFormat("not a bijection")));
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            store.put_key("alice", &Key::generate()).unwrap();
+            let mode = std::fs::metadata(dir.path().join("keys/alice.key")).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+            let mode = std::fs::metadata(dir.path().join("keys")).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o700);
+        }
+    }
+
+    #[test]
+    fn test_file_key_store_replaces_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = FileKeyStore::open(dir.path()).unwrap();
+        let key = Key::generate();
+        store.put_key("alice", &Key::generate()).unwrap();
+        store.put_key("alice", &key).unwrap();
+        assert_eq!(store.get_key("alice").unwrap(), Some(key));
+        let names: Vec<_> = std::fs::read_dir(dir.path()).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+        assert_eq!(names, ["alice.key"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_file_key_store_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // Existing directories are never re-moded, even when their group or others can access them.
+        let dir = tempfile::tempdir().unwrap();
+        for mode in [0o750, 0o500] {
+            std::fs::set_permissions(dir.path(), std::fs::Permissions::from_mode(mode)).unwrap();
+            assert!(FileKeyStore::open(dir.path()).is_ok());
+            assert_eq!(std::fs::metadata(dir.path()).unwrap().permissions().mode() & 0o777, mode);
+        }
+
+        // Files created with looser permissions by something else are not replaced as they are.
+        std::fs::set_permissions(dir.path(), std::fs::Permissions::from_mode(0o700)).unwrap();
+        std::fs::write(dir.path().join("alice.key"), Key::generate().to_bytes()).unwrap();
+        std::fs::set_permissions(dir.path().join("alice.key"), std::fs::Permissions::from_mode(0o644)).unwrap();
+        let mut store = FileKeyStore::open(dir.path()).unwrap();
+        store.put_key("alice", &Key::generate()).unwrap();
+        assert_eq!(std::fs::metadata(dir.path().join("alice.key")).unwrap().permissions().mode() & 0o777, 0o600);
+    }
+
+    #[test]
+    fn test_passphrase_wrapping() {
+        let dir = tempfile::tempdir().unwrap();
+        let key = Key::generate();
+        let mut store = FileKeyStore::open(dir.path()).unwrap().with_passphrase("correct horse");
+        store.put_key("alice", &key).unwrap();
+        assert_eq!(store.get_key("alice").unwrap(), Some(key.clone()));
+
+        let stored = std::fs::read(dir.path().join("alice.key")).unwrap();
+        assert!(!stored.windows(key.to_bytes().len()).any(|window| window == key.to_bytes()));
+        assert_eq!(FileKeyStore::open(dir.path()).unwrap().get_key("alice"), Err(Error::PassphraseRequired));
+        let wrong_store = FileKeyStore::open(dir.path()).unwrap().with_passphrase("battery staple");
+        assert_eq!(wrong_store.get_key("alice"), Err(Error::IntegrityCheckFailed));
+        // Plaintext files planted next to the wrapped ones are refused rather than trusted.
+        std::fs::write(dir.path().join("mallory.key"), Key::generate().to_bytes()).unwrap();
+        assert_eq!(store.get_key("mallory"), Err(Error::InvalidFormat("key file is not passphrase-wrapped")));
+        let re_encryption_key = ReEncryptionKey::generate(&key, &Key::generate()).unwrap();
+        std::fs::write(dir.path().join("mallory.rekey"), re_encryption_key.to_bytes()).unwrap();
+        assert_eq!(store.get_re_encryption_key("mallory"), Err(Error::InvalidFormat("key file is not passphrase-wrapped")));
+    }
+}
@@ -1,19 +1,62 @@
 #![allow(incomplete_features)]
 #![feature(generic_const_exprs)]
-#![doc = include_str!("../README.md")]
+#![cfg_attr(feature = "std", doc = include_str!("../README.md"))]
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
 
 mod permutations;
 pub mod keygen;
 pub mod encryption;
+pub mod envelope;
+pub mod error;
+#[cfg(feature = "std")]
+pub mod keyring;
+#[cfg(feature = "std")]
+pub mod updatable;
+#[cfg(feature = "std")]
+pub mod hybrid;
+#[cfg(feature = "std")]
+pub mod inspect;
+#[cfg(feature = "std")]
+pub mod keystore;
+#[cfg(feature = "std")]
+pub mod proxy;
+#[cfg(feature = "std")]
+pub mod wrapping;
+#[cfg(feature = "http")]
+pub mod http;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "python")]
+pub mod python;
 mod utils;
+mod serialization;
 mod aont;
+#[cfg(feature = "analysis")]
+pub mod analysis;
+#[cfg(feature = "capi")]
+pub mod capi;
 
+pub use aont::{AontHash, AontKind};
+pub use permutations::Permutation;
 pub use encryption::*;
 pub use keygen::*;
+pub use envelope::*;
+#[cfg(feature = "std")]
+pub use keyring::*;
+#[cfg(feature = "std")]
+pub use keystore::{FileKeyStore, KeyStore, MemoryKeyStore};
+#[cfg(feature = "std")]
+pub use inspect::{inspect, CiphertextInfo};
+#[cfg(feature = "std")]
+pub use wrapping::WrappedKey;
+pub use error::Error;
 
 const BLOCK_SIZE_BITS: usize = 32;
 const BLOCK_SIZE_BYTE: usize = BLOCK_SIZE_BITS >> 3;
 const MESSAGE_BLOCKS_COUNT: usize = 8;
 
 pub type Encrypted = [u8; (MESSAGE_BLOCKS_COUNT + 1) * BLOCK_SIZE_BYTE];
-pub type Plaintext = [u8; MESSAGE_BLOCKS_COUNT * BLOCK_SIZE_BYTE];
\ No newline at end of file
+pub type Plaintext = [u8; MESSAGE_BLOCKS_COUNT * BLOCK_SIZE_BYTE];
+pub type Fingerprint = [u8; 8];
\ No newline at end of file
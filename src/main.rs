@@ -1,6 +1,94 @@
-use symmetric_pre::{decrypt, encrypt, re_encrypt, Key, ReEncryptionKey};
+use std::io::{Read, Write};
+use std::process::exit;
+use symmetric_pre::{decrypt, encrypt, re_encrypt, Envelope, FileKeyStore, Key, KeyStore, Plaintext, ReEncryptionKey, Scheme};
+
+const USAGE: &str = "usage: symmetric_pre [--store <dir> (keygen <id> | rekey <from> <to> <id> | encrypt <key> | decrypt <key> | re-encrypt <rekey> | list)]";
+const PASSPHRASE_VARIABLE: &str = "SYMMETRIC_PRE_PASSPHRASE";
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.as_slice() {
+        [] => demo(),
+        [flag, dir, command @ ..] if flag == "--store" => {
+            let mut store = open_store(dir);
+            if let Err(e) = run(&mut store, command) {
+                eprintln!("{}", e);
+                exit(1);
+            }
+        }
+        _ => usage(),
+    }
+}
+
+fn run(store: &mut FileKeyStore, command: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let command: Vec<&str> = command.iter().map(String::as_str).collect();
+    match command.as_slice() {
+        ["keygen", id] => store.put_key(id, &Key::generate())?,
+        ["rekey", from, to, id] => {
+            let from = load_key(store, from)?;
+            let to = load_key(store, to)?;
+            store.put_re_encryption_key(id, &ReEncryptionKey::generate(&from, &to)?)?;
+        }
+        ["encrypt", id] => {
+            let key = load_key(store, id)?;
+            let mut input = Vec::new();
+            std::io::stdin().read_to_end(&mut input)?;
+            let message: Plaintext = input
+                .as_slice()
+                .try_into()
+                .map_err(|_| format!("the message must be exactly {} bytes long, not {}", size_of::<Plaintext>(), input.len()))?;
+            let envelope = Scheme::new().encrypt_envelope(&message, &key);
+            std::io::stdout().write_all(&envelope.to_bytes())?;
+        }
+        ["decrypt", id] => {
+            let key = load_key(store, id)?;
+            let plaintext = read_envelope()?.decrypt(&key)?;
+            std::io::stdout().write_all(&plaintext)?;
+        }
+        ["re-encrypt", id] => {
+            let Some(re_encryption_key) = store.get_re_encryption_key(id)? else {
+                return Err(format!("no re-encryption key {}", id).into());
+            };
+            let envelope = read_envelope()?.re_encrypt(&re_encryption_key)?;
+            std::io::stdout().write_all(&envelope.to_bytes())?;
+        }
+        ["list"] => {
+            for id in store.list_keys()? {
+                println!("key {}", id);
+            }
+            for id in store.list_re_encryption_keys()? {
+                println!("rekey {}", id);
+            }
+        }
+        _ => usage(),
+    }
+    Ok(())
+}
+
+fn open_store(dir: &str) -> FileKeyStore {
+    match FileKeyStore::open(dir) {
+        Ok(store) => match std::env::var(PASSPHRASE_VARIABLE) {
+            Ok(passphrase) => store.with_passphrase(&passphrase),
+            Err(_) => store,
+        },
+        Err(e) => {
+            eprintln!("cannot open key store {}: {}", dir, e);
+            exit(1);
+        }
+    }
+}
+
+fn load_key(store: &FileKeyStore, id: &str) -> Result<Key, Box<dyn std::error::Error>> {
+    store.get_key(id)?.ok_or_else(|| format!("no key {}", id).into())
+}
+
+fn read_envelope() -> Result<Envelope, Box<dyn std::error::Error>> {
+    let mut bytes = Vec::new();
+    std::io::stdin().read_to_end(&mut bytes)?;
+    Ok(Envelope::from_bytes(&bytes)?)
+}
+
+fn demo() {
     let message = b"les sanglots longs des violons !";
     println!("message: {:?}", message);
     let key1 = Key::generate();
@@ -10,9 +98,14 @@ fn main() {
     println!("decrypted: {:?}", str::from_utf8(&decrypted).unwrap());
 
     let key2 = Key::generate();
-    let re_encryption_key = ReEncryptionKey::generate(&key1, &key2);
+    let re_encryption_key = ReEncryptionKey::generate(&key1, &key2).unwrap();
     let re_encrypted = re_encrypt(&encrypted, &re_encryption_key);
     println!("re_encrypted: {:?}", re_encrypted);
     let re_decrypted = decrypt(&re_encrypted, &key2);
     println!("re_decrypted: {:?}", str::from_utf8(&re_decrypted).unwrap());
 }
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    exit(2);
+}
@@ -1,13 +1,21 @@
+use crate::error::Error;
+use alloc::vec::Vec;
 use collar::CollectArray;
+#[cfg(feature = "lehmer")]
 use num_bigint::BigUint;
+#[cfg(feature = "lehmer")]
 use num_traits::Zero;
+use rand::{CryptoRng, Rng};
+use sha3::digest::{ExtendableOutput, Update, XofReader};
+use sha3::Shake256;
+#[cfg(feature = "constant-time")]
+use subtle::{ConditionallySelectable, ConstantTimeEq};
 
-pub(crate) fn permute_block<const BIT_COUNT: usize>(input: &[u8; BIT_COUNT >> 3], permutation_key: &[usize; BIT_COUNT]) -> [u8; BIT_COUNT >> 3] {
-    let bit_count = input.len() << 3;
-    let mut output = [0u8; BIT_COUNT >> 3];
-    for i in 0..bit_count {
-        let next_pos = permutation_key[i];
-        assert!(next_pos < bit_count);
+#[cfg(not(feature = "constant-time"))]
+pub(crate) fn permute_block<const BIT_COUNT: usize>(input: &[u8; BIT_COUNT.div_ceil(8)], permutation_key: &[usize; BIT_COUNT]) -> [u8; BIT_COUNT.div_ceil(8)] {
+    let mut output = [0u8; BIT_COUNT.div_ceil(8)];
+    for (i, &next_pos) in permutation_key.iter().enumerate() {
+        assert!(next_pos < BIT_COUNT);
         let input_byte_pos = next_pos >> 3;
         let input_bit_pos = 7 - (next_pos & 7);
         let input_bit = (input[input_byte_pos] >> input_bit_pos) & 1;
@@ -18,12 +26,11 @@ pub(crate) fn permute_block<const BIT_COUNT: usize>(input: &[u8; BIT_COUNT >> 3]
     output
 }
 
-pub(crate) fn depermute_block<const BIT_COUNT: usize>(input: &[u8; BIT_COUNT >> 3], permutation_key: &[usize; BIT_COUNT]) -> [u8; BIT_COUNT >> 3] {
-    let bit_count = input.len() << 3;
-    let mut output = [0u8; BIT_COUNT >> 3];
-    for i in 0..bit_count {
-        let next_pos = permutation_key[i];
-        assert!(next_pos < bit_count);
+#[cfg(not(feature = "constant-time"))]
+pub(crate) fn depermute_block<const BIT_COUNT: usize>(input: &[u8; BIT_COUNT.div_ceil(8)], permutation_key: &[usize; BIT_COUNT]) -> [u8; BIT_COUNT.div_ceil(8)] {
+    let mut output = [0u8; BIT_COUNT.div_ceil(8)];
+    for (i, &next_pos) in permutation_key.iter().enumerate() {
+        assert!(next_pos < BIT_COUNT);
         let input_byte_pos = i >> 3;
         let input_bit_pos = 7 - (i & 7);
         let input_bit = (input[input_byte_pos] >> input_bit_pos) & 1;
@@ -34,7 +41,8 @@ pub(crate) fn depermute_block<const BIT_COUNT: usize>(input: &[u8; BIT_COUNT >>
     output
 }
 
-pub(crate) fn permute_block_set<'a, const BLOCKS_COUNT: usize, const BYTES_PER_BLOCK: usize>(input_blocks: &[&'a [u8; BYTES_PER_BLOCK]; BLOCKS_COUNT], permutation_key: &[usize; BLOCKS_COUNT]) -> [&'a [u8; BYTES_PER_BLOCK]; BLOCKS_COUNT] {
+#[cfg(not(feature = "constant-time"))]
+pub(crate) fn permute_block_set<const BLOCKS_COUNT: usize, const BYTES_PER_BLOCK: usize>(input_blocks: &[[u8; BYTES_PER_BLOCK]; BLOCKS_COUNT], permutation_key: &[usize; BLOCKS_COUNT]) -> [[u8; BYTES_PER_BLOCK]; BLOCKS_COUNT] {
     let blocks_count = input_blocks.len();
     let mut output = *input_blocks;
     for i in 0..blocks_count {
@@ -45,7 +53,8 @@ pub(crate) fn permute_block_set<'a, const BLOCKS_COUNT: usize, const BYTES_PER_B
     output
 }
 
-pub(crate) fn depermute_block_set<'a, const BLOCKS_COUNT: usize, const BYTES_PER_BLOCK: usize>(input_blocks: &[&'a [u8; BYTES_PER_BLOCK]; BLOCKS_COUNT], permutation_key: &[usize; BLOCKS_COUNT]) -> [&'a [u8; BYTES_PER_BLOCK]; BLOCKS_COUNT] {
+#[cfg(not(feature = "constant-time"))]
+pub(crate) fn depermute_block_set<const BLOCKS_COUNT: usize, const BYTES_PER_BLOCK: usize>(input_blocks: &[[u8; BYTES_PER_BLOCK]; BLOCKS_COUNT], permutation_key: &[usize; BLOCKS_COUNT]) -> [[u8; BYTES_PER_BLOCK]; BLOCKS_COUNT] {
     let blocks_count = input_blocks.len();
     let mut output = *input_blocks;
     for i in 0..blocks_count {
@@ -56,55 +65,348 @@ pub(crate) fn depermute_block_set<'a, const BLOCKS_COUNT: usize, const BYTES_PER
     output
 }
 
-pub(crate) fn find_permute_conversion<const BIT_COUNT: usize>(old_permutation: &[usize; BIT_COUNT], new_permutation: &[usize; BIT_COUNT]) -> [usize; BIT_COUNT] {
-    let mut conversion = [0; BIT_COUNT];
+// The constant-time versions read every input position for every output position and keep the one whose index
+// matches the permutation with a masked selection, so neither branches nor memory accesses depend on the key.
+#[cfg(feature = "constant-time")]
+fn block_bits<const BIT_COUNT: usize>(input: &[u8; BIT_COUNT.div_ceil(8)]) -> [u8; BIT_COUNT] {
+    let mut bits = [0u8; BIT_COUNT];
     for i in 0..BIT_COUNT {
-        for j in 0..BIT_COUNT {
-            if old_permutation[i] == new_permutation[j] {
-                conversion[j] = i;
-                break;
+        bits[i] = (input[i >> 3] >> (7 - (i & 7))) & 1;
+    }
+    bits
+}
+
+#[cfg(feature = "constant-time")]
+pub(crate) fn permute_block<const BIT_COUNT: usize>(input: &[u8; BIT_COUNT.div_ceil(8)], permutation_key: &[usize; BIT_COUNT]) -> [u8; BIT_COUNT.div_ceil(8)] {
+    let input_bits = block_bits::<BIT_COUNT>(input);
+    let mut output = [0u8; BIT_COUNT.div_ceil(8)];
+    for i in 0..BIT_COUNT {
+        let mut bit = 0u8;
+        for (j, input_bit) in input_bits.iter().enumerate() {
+            bit.conditional_assign(input_bit, permutation_key[i].ct_eq(&j));
+        }
+        output[i >> 3] |= bit << (7 - (i & 7));
+    }
+    output
+}
+
+#[cfg(feature = "constant-time")]
+pub(crate) fn depermute_block<const BIT_COUNT: usize>(input: &[u8; BIT_COUNT.div_ceil(8)], permutation_key: &[usize; BIT_COUNT]) -> [u8; BIT_COUNT.div_ceil(8)] {
+    let input_bits = block_bits::<BIT_COUNT>(input);
+    let mut output = [0u8; BIT_COUNT.div_ceil(8)];
+    for i in 0..BIT_COUNT {
+        let mut bit = 0u8;
+        for (input_bit, index) in input_bits.iter().zip(permutation_key) {
+            bit.conditional_assign(input_bit, index.ct_eq(&i));
+        }
+        output[i >> 3] |= bit << (7 - (i & 7));
+    }
+    output
+}
+
+#[cfg(feature = "constant-time")]
+pub(crate) fn permute_block_set<const BLOCKS_COUNT: usize, const BYTES_PER_BLOCK: usize>(input_blocks: &[[u8; BYTES_PER_BLOCK]; BLOCKS_COUNT], permutation_key: &[usize; BLOCKS_COUNT]) -> [[u8; BYTES_PER_BLOCK]; BLOCKS_COUNT] {
+    let mut output = [[0u8; BYTES_PER_BLOCK]; BLOCKS_COUNT];
+    for (output_block, index) in output.iter_mut().zip(permutation_key) {
+        for (j, input_block) in input_blocks.iter().enumerate() {
+            output_block.conditional_assign(input_block, index.ct_eq(&j));
+        }
+    }
+    output
+}
+
+#[cfg(feature = "constant-time")]
+pub(crate) fn depermute_block_set<const BLOCKS_COUNT: usize, const BYTES_PER_BLOCK: usize>(input_blocks: &[[u8; BYTES_PER_BLOCK]; BLOCKS_COUNT], permutation_key: &[usize; BLOCKS_COUNT]) -> [[u8; BYTES_PER_BLOCK]; BLOCKS_COUNT] {
+    let mut output = [[0u8; BYTES_PER_BLOCK]; BLOCKS_COUNT];
+    for (i, output_block) in output.iter_mut().enumerate() {
+        for (input_block, index) in input_blocks.iter().zip(permutation_key) {
+            output_block.conditional_assign(input_block, index.ct_eq(&i));
+        }
+    }
+    output
+}
+
+// old_permutation[conversion[j]] == new_permutation[j], so conversion is the inverse of old_permutation composed with
+// new_permutation.
+pub(crate) fn find_permute_conversion<const BIT_COUNT: usize>(old_permutation: &[usize; BIT_COUNT], new_permutation: &[usize; BIT_COUNT]) -> [usize; BIT_COUNT] {
+    compose_permutations(&invert_permutation(old_permutation), new_permutation)
+}
+
+#[cfg(not(feature = "constant-time"))]
+fn invert_permutation<const ELEMENTS_COUNT: usize>(permutation: &[usize; ELEMENTS_COUNT]) -> [usize; ELEMENTS_COUNT] {
+    let mut inverse = [0; ELEMENTS_COUNT];
+    for (i, &index) in permutation.iter().enumerate() {
+        inverse[index] = i;
+    }
+    inverse
+}
+
+#[cfg(not(feature = "constant-time"))]
+pub(crate) fn compose_permutations<const ELEMENTS_COUNT: usize>(first: &[usize; ELEMENTS_COUNT], second: &[usize; ELEMENTS_COUNT]) -> [usize; ELEMENTS_COUNT] {
+    let mut composed = [0usize; ELEMENTS_COUNT];
+    for (element, &index) in composed.iter_mut().zip(second) {
+        *element = first[index];
+    }
+    composed
+}
+
+#[cfg(not(feature = "constant-time"))]
+fn swap_elements<const ELEMENTS_COUNT: usize>(permutation: &mut [usize; ELEMENTS_COUNT], i: usize, j: usize) {
+    permutation.swap(i, j);
+}
+
+// Like the block permutations, the constant-time versions go through every position and select the one matching the
+// secret index, so that inverting, composing and sampling keys do not access memory at key-dependent addresses.
+#[cfg(feature = "constant-time")]
+fn invert_permutation<const ELEMENTS_COUNT: usize>(permutation: &[usize; ELEMENTS_COUNT]) -> [usize; ELEMENTS_COUNT] {
+    let mut inverse = [0; ELEMENTS_COUNT];
+    for (j, element) in inverse.iter_mut().enumerate() {
+        let mut value = 0u64;
+        for (i, index) in permutation.iter().enumerate() {
+            value.conditional_assign(&(i as u64), index.ct_eq(&j));
+        }
+        *element = value as usize;
+    }
+    inverse
+}
+
+#[cfg(feature = "constant-time")]
+pub(crate) fn compose_permutations<const ELEMENTS_COUNT: usize>(first: &[usize; ELEMENTS_COUNT], second: &[usize; ELEMENTS_COUNT]) -> [usize; ELEMENTS_COUNT] {
+    let mut composed = [0usize; ELEMENTS_COUNT];
+    for (element, index) in composed.iter_mut().zip(second) {
+        let mut value = 0u64;
+        for (j, &first_element) in first.iter().enumerate() {
+            value.conditional_assign(&(first_element as u64), index.ct_eq(&j));
+        }
+        *element = value as usize;
+    }
+    composed
+}
+
+#[cfg(feature = "constant-time")]
+fn swap_elements<const ELEMENTS_COUNT: usize>(permutation: &mut [usize; ELEMENTS_COUNT], i: usize, j: usize) {
+    for k in 0..ELEMENTS_COUNT {
+        let choice = k.ct_eq(&j);
+        let (element_i, element_k) = (permutation[i] as u64, permutation[k] as u64);
+        permutation[i] = u64::conditional_select(&element_i, &element_k, choice) as usize;
+        permutation[k] = u64::conditional_select(&element_k, &element_i, choice) as usize;
+    }
+}
+
+pub(crate) fn random_permutation<const ELEMENTS_COUNT: usize, R: CryptoRng + Rng + ?Sized>(rng: &mut R) -> [usize; ELEMENTS_COUNT] {
+    let mut permutation: [usize; ELEMENTS_COUNT] = (0..ELEMENTS_COUNT).collect_array();
+    for i in (1..ELEMENTS_COUNT).rev() {
+        swap_elements(&mut permutation, i, uniform_index(|| rng.next_u32(), i + 1));
+    }
+    permutation
+}
+
+const SEED_DOMAIN: &[u8] = b"symmetric_pre permutation";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Permutation<const N: usize>([usize; N]);
+
+impl<const N: usize> Permutation<N> {
+    pub fn identity() -> Self {
+        Self((0..N).collect_array())
+    }
+
+    pub fn random<R: CryptoRng + Rng + ?Sized>(rng: &mut R) -> Self {
+        Self(random_permutation(rng))
+    }
+
+    // Fisher–Yates driven by SHAKE256 over the seed, drawing each index by rejection sampling so that it is exactly
+    // uniform, as `random` does with the RNG's output.
+    pub fn from_seed_bytes(seed: &[u8]) -> Self {
+        let mut shake = Shake256::default();
+        shake.update(SEED_DOMAIN);
+        shake.update(seed);
+        let mut reader = shake.finalize_xof();
+        let mut next_u32 = || {
+            let mut bytes = [0u8; 4];
+            reader.read(&mut bytes);
+            u32::from_be_bytes(bytes)
+        };
+        let mut permutation: [usize; N] = (0..N).collect_array();
+        for i in (1..N).rev() {
+            swap_elements(&mut permutation, i, uniform_index(&mut next_u32, i + 1));
+        }
+        Self(permutation)
+    }
+
+    pub fn new(permutation: [usize; N]) -> Result<Self, Error> {
+        if !is_bijection(&permutation) {
+            return Err(Error::InvalidFormat("not a bijection"));
+        }
+        Ok(Self(permutation))
+    }
+
+    pub fn as_array(&self) -> &[usize; N] {
+        &self.0
+    }
+
+    // Applying `self.compose(other)` is the same as applying `self`, then `other`.
+    pub fn compose(&self, other: &Self) -> Self {
+        Self(compose_permutations(&self.0, &other.0))
+    }
+
+    pub fn inverse(&self) -> Self {
+        Self(find_permute_conversion(&self.0, &Self::identity().0))
+    }
+
+    // A block of N bits takes N.div_ceil(8) bytes, the padding bits of its last byte are ignored and left at zero.
+    pub fn apply_bits(&self, input: &[u8; N.div_ceil(8)]) -> [u8; N.div_ceil(8)] {
+        permute_block::<N>(input, &self.0)
+    }
+
+    pub fn apply_bits_inverse(&self, input: &[u8; N.div_ceil(8)]) -> [u8; N.div_ceil(8)] {
+        depermute_block::<N>(input, &self.0)
+    }
+
+    pub fn apply_blocks<const BYTES_PER_BLOCK: usize>(&self, blocks: &[[u8; BYTES_PER_BLOCK]; N]) -> [[u8; BYTES_PER_BLOCK]; N] {
+        permute_block_set(blocks, &self.0)
+    }
+
+    pub fn apply_blocks_inverse<const BYTES_PER_BLOCK: usize>(&self, blocks: &[[u8; BYTES_PER_BLOCK]; N]) -> [[u8; BYTES_PER_BLOCK]; N] {
+        depermute_block_set(blocks, &self.0)
+    }
+
+    // Cycles of i -> self[i], fixed points included, each one starting from its smallest element.
+    pub fn cycles(&self) -> Vec<Vec<usize>> {
+        let mut visited = [false; N];
+        let mut cycles = Vec::new();
+        for start in 0..N {
+            if visited[start] {
+                continue;
             }
+            let mut cycle = Vec::new();
+            let mut i = start;
+            while !visited[i] {
+                visited[i] = true;
+                cycle.push(i);
+                i = self.0[i];
+            }
+            cycles.push(cycle);
         }
+        cycles
+    }
+
+    #[cfg(feature = "lehmer")]
+    pub fn to_lehmer(&self) -> BigUint {
+        get_permutation_number(self.0)
+    }
+
+    #[cfg(feature = "lehmer")]
+    pub fn from_lehmer(n: &BigUint) -> Result<Self, Error> {
+        Self::from_lehmer_bytes(&n.to_bytes_be())
     }
-    conversion
+
+    pub(crate) fn from_lehmer_bytes(n: &[u8]) -> Result<Self, Error> {
+        Ok(Self(generate_permutation(n)?))
+    }
+}
+
+fn is_bijection<const N: usize>(permutation: &[usize; N]) -> bool {
+    let mut seen = [false; N];
+    for &index in permutation {
+        if index >= N || seen[index] {
+            return false;
+        }
+        seen[index] = true;
+    }
+    true
 }
 
-pub(crate) fn generate_permutation<const ELEMENTS_COUNT: usize>(mut n: BigUint) -> [usize; ELEMENTS_COUNT] {
+fn uniform_index(mut next_u32: impl FnMut() -> u32, bound: usize) -> usize {
+    let bound = bound as u64;
+    // Above 2^32, `limit` would be zero and every value would be drawn again.
+    assert!(bound > 0 && bound <= 1 << 32, "cannot draw an index below {} from 32-bit values", bound);
+    // Values from `limit` up to 2^32 would make the lowest residues more likely, so they are drawn again.
+    let limit = (1 << 32) - (1 << 32) % bound;
+    loop {
+        let value = next_u32() as u64;
+        if value < limit {
+            return (value % bound) as usize;
+        }
+    }
+}
+
+// `n` is the Lehmer code in big-endian bytes, divided in place by 1, 2, ... so that version 1 keys can be read
+// without the lehmer feature.
+pub(crate) fn generate_permutation<const ELEMENTS_COUNT: usize>(n: &[u8]) -> Result<[usize; ELEMENTS_COUNT], Error> {
+    let mut n = n.to_vec();
     let mut stack: Vec<usize> = Vec::new();
     let mut result = [0usize; ELEMENTS_COUNT];
     let mut input_elements: [usize; ELEMENTS_COUNT] = (0..ELEMENTS_COUNT).collect_array();
 
     for i in 1..=ELEMENTS_COUNT {
-        stack.push((n.clone() % i).try_into().unwrap());
-        n = n / i;
+        let mut remainder = 0usize;
+        for byte in n.iter_mut() {
+            let value = remainder << 8 | *byte as usize;
+            *byte = (value / i) as u8;
+            remainder = value % i;
+        }
+        stack.push(remainder);
+    }
+    if n.iter().any(|&byte| byte != 0) {
+        return Err(Error::InvalidFormat("Lehmer code out of range"));
     }
 
-    for i in 0..ELEMENTS_COUNT {
+    for element in result.iter_mut() {
         let a = stack.pop().unwrap();
-        result[i] = input_elements[a];
+        *element = input_elements[a];
         for j in a..ELEMENTS_COUNT - 1 {
             input_elements[j] = input_elements[j + 1];
         }
     }
-    result
+    Ok(result)
 }
 
+#[cfg(feature = "lehmer")]
 pub(crate) fn get_permutation_number<const ELEMENTS_COUNT: usize>(permutation: [usize; ELEMENTS_COUNT]) -> BigUint {
-    let mut input_elements: Vec<usize> = (0..ELEMENTS_COUNT).collect();
+    // Each Lehmer digit is the number of elements not used yet that are smaller than the current one, counted with a
+    // Fenwick tree over the elements already used.
+    let mut used = [0usize; ELEMENTS_COUNT];
     let mut n = BigUint::zero();
 
-    for i in 0..ELEMENTS_COUNT {
-        let a = input_elements.iter().position(|&x| x == permutation[i]).unwrap();
+    for (i, &element) in permutation.iter().enumerate() {
+        let a = element - fenwick_prefix_sum(&used, element);
         n = n * (ELEMENTS_COUNT - i) + BigUint::from(a);
-        input_elements.remove(a);
+        fenwick_increment(&mut used, element);
     }
 
     n
 }
 
+#[cfg(feature = "lehmer")]
+fn fenwick_increment(tree: &mut [usize], index: usize) {
+    let mut i = index + 1;
+    while i <= tree.len() {
+        tree[i - 1] += 1;
+        i += i & i.wrapping_neg();
+    }
+}
+
+// Sum of the values at indices below `end`.
+#[cfg(feature = "lehmer")]
+fn fenwick_prefix_sum(tree: &[usize], end: usize) -> usize {
+    let mut sum = 0;
+    let mut i = end;
+    while i > 0 {
+        sum += tree[i - 1];
+        i -= i & i.wrapping_neg();
+    }
+    sum
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::error::Error;
+    use alloc::vec;
+    use alloc::vec::Vec;
+    #[cfg(feature = "lehmer")]
     use num_bigint::BigUint;
+    #[cfg(feature = "lehmer")]
     use num_traits::Zero;
 
     #[test]
@@ -131,16 +433,245 @@ mod tests {
         assert_eq!(conversion, [1, 0, 2, 3, 4, 5, 7, 6, 8, 9, 10, 11, 12, 13, 15, 14]);
     }
 
+    // The previous O(N^2) implementations, kept as references.
+    fn find_permute_conversion_reference<const N: usize>(old_permutation: &[usize; N], new_permutation: &[usize; N]) -> [usize; N] {
+        let mut conversion = [0; N];
+        for (i, old_element) in old_permutation.iter().enumerate() {
+            for (j, new_element) in new_permutation.iter().enumerate() {
+                if old_element == new_element {
+                    conversion[j] = i;
+                    break;
+                }
+            }
+        }
+        conversion
+    }
+
+    #[cfg(feature = "lehmer")]
+    fn get_permutation_number_reference<const N: usize>(permutation: [usize; N]) -> BigUint {
+        let mut input_elements: Vec<usize> = (0..N).collect();
+        let mut n = BigUint::zero();
+
+        for (i, element) in permutation.iter().enumerate() {
+            let a = input_elements.iter().position(|x| x == element).unwrap();
+            n = n * (N - i) + BigUint::from(a);
+            input_elements.remove(a);
+        }
+
+        n
+    }
+
+    fn check_find_permute_conversion<const N: usize>() {
+        for seed in 0u32..50 {
+            let old_permutation = super::Permutation::<N>::from_seed_bytes(&[b"old".as_slice(), &seed.to_be_bytes()].concat());
+            let new_permutation = super::Permutation::<N>::from_seed_bytes(&[b"new".as_slice(), &seed.to_be_bytes()].concat());
+            assert_eq!(
+                super::find_permute_conversion(old_permutation.as_array(), new_permutation.as_array()),
+                find_permute_conversion_reference(old_permutation.as_array(), new_permutation.as_array()),
+            );
+        }
+    }
+
+    #[test]
+    fn test_find_permute_conversion_matches_reference() {
+        check_find_permute_conversion::<1>();
+        check_find_permute_conversion::<2>();
+        check_find_permute_conversion::<9>();
+        check_find_permute_conversion::<32>();
+        check_find_permute_conversion::<256>();
+        check_find_permute_conversion::<1000>();
+    }
+
+    #[test]
+    fn test_compose_permutations() {
+        let input = [0b1000_0001, 0b0100_0010];
+        let first = [0, 1, 2, 3, 4, 5, 7, 6, 8, 9, 10, 11, 12, 13, 15, 14];
+        let second = [15, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14];
+        let composed = super::compose_permutations(&first, &second);
+        assert_eq!(composed, [14, 0, 1, 2, 3, 4, 5, 7, 6, 8, 9, 10, 11, 12, 13, 15]);
+        let two_steps = super::permute_block(&super::permute_block(&input, &first), &second);
+        let one_step = super::permute_block(&input, &composed);
+        assert_eq!(two_steps, one_step);
+    }
+
+    #[cfg(feature = "lehmer")]
+    fn check_get_permutation_number<const N: usize>() {
+        for seed in 0u32..50 {
+            let permutation = super::Permutation::<N>::from_seed_bytes(&seed.to_be_bytes());
+            let n = super::get_permutation_number(*permutation.as_array());
+            assert_eq!(n, get_permutation_number_reference(*permutation.as_array()));
+            assert_eq!(super::generate_permutation::<N>(&n.to_bytes_be()), Ok(*permutation.as_array()));
+        }
+    }
+
+    #[cfg(feature = "lehmer")]
+    #[test]
+    fn test_get_permutation_number_matches_reference() {
+        check_get_permutation_number::<1>();
+        check_get_permutation_number::<2>();
+        check_get_permutation_number::<9>();
+        check_get_permutation_number::<32>();
+        check_get_permutation_number::<256>();
+        check_get_permutation_number::<1000>();
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_random_permutation() {
+        let mut rng = rand::thread_rng();
+        let mut seen = [false; 16];
+        for _ in 0..64 {
+            let permutation = super::random_permutation::<16, _>(&mut rng);
+            let mut sorted = permutation;
+            sorted.sort_unstable();
+            assert_eq!(sorted, [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]);
+            seen[permutation[0]] = true;
+        }
+        assert!(seen.iter().filter(|&&seen| seen).count() > 1);
+    }
+
+    // 99.9th percentiles of the chi-square distribution with 8 * 7, 32 * 31 and 4! - 1 degrees of freedom.
+    const CHI_SQUARE_56: f64 = 94.46;
+    const CHI_SQUARE_992: f64 = 1135.0;
+    const CHI_SQUARE_23: f64 = 49.73;
+
+    // Chi-square statistic of how often each element lands on each position, summed over positions: each position
+    // contributes N - 1 degrees of freedom.
+    fn position_chi_square<const N: usize>(permutations: impl Iterator<Item = super::Permutation<N>>) -> f64 {
+        let mut counts = [[0usize; N]; N];
+        let mut samples = 0;
+        for permutation in permutations {
+            for (position, &element) in permutation.as_array().iter().enumerate() {
+                counts[position][element] += 1;
+            }
+            samples += 1;
+        }
+        let expected = samples as f64 / N as f64;
+        counts.iter().flatten().map(|&count| (count as f64 - expected).powi(2) / expected).sum()
+    }
+
+    // Chi-square statistic over the N! permutations themselves, with N! - 1 degrees of freedom.
+    fn permutation_chi_square<const N: usize>(permutations: impl Iterator<Item = super::Permutation<N>>) -> f64 {
+        let mut counts = alloc::collections::BTreeMap::new();
+        let mut samples = 0;
+        for permutation in permutations {
+            *counts.entry(permutation).or_insert(0usize) += 1;
+            samples += 1;
+        }
+        let outcomes: usize = (1..=N).product();
+        assert_eq!(counts.len(), outcomes);
+        let expected = samples as f64 / outcomes as f64;
+        counts.values().map(|&count| (count as f64 - expected).powi(2) / expected).sum()
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_permutation_random_is_uniform() {
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        let mut rng = StdRng::seed_from_u64(1);
+        assert!(position_chi_square((0..20000).map(|_| super::Permutation::<8>::random(&mut rng))) < CHI_SQUARE_56);
+        assert!(permutation_chi_square((0..24000).map(|_| super::Permutation::<4>::random(&mut rng))) < CHI_SQUARE_23);
+    }
+
+    #[test]
+    fn test_permutation_from_seed_bytes_is_uniform() {
+        let seeds = |count: u32| (0..count).map(|i| i.to_be_bytes());
+        assert!(position_chi_square(seeds(20000).map(|seed| super::Permutation::<8>::from_seed_bytes(&seed))) < CHI_SQUARE_56);
+        assert!(position_chi_square(seeds(20000).map(|seed| super::Permutation::<32>::from_seed_bytes(&seed))) < CHI_SQUARE_992);
+        assert!(permutation_chi_square(seeds(24000).map(|seed| super::Permutation::<4>::from_seed_bytes(&seed))) < CHI_SQUARE_23);
+    }
+
+    #[test]
+    fn test_permutation_new() {
+        assert!(super::Permutation::new([2, 0, 1]).is_ok());
+        assert_eq!(super::Permutation::new([2, 0, 0]), Err(Error::InvalidFormat("not a bijection")));
+        assert_eq!(super::Permutation::new([3, 0, 1]), Err(Error::InvalidFormat("not a bijection")));
+    }
+
+    #[test]
+    fn test_permutation_compose_inverse() {
+        let input = [0b1000_0001, 0b0100_0010];
+        let first = super::Permutation::from_seed_bytes(b"first");
+        let second = super::Permutation::<16>::from_seed_bytes(b"second");
+        let composed = first.compose(&second);
+        assert_eq!(composed.apply_bits(&input), second.apply_bits(&first.apply_bits(&input)));
+        assert_eq!(first.compose(&first.inverse()), super::Permutation::identity());
+        assert_eq!(first.inverse().compose(&first), super::Permutation::identity());
+        assert_eq!(first.inverse().apply_bits(&input), first.apply_bits_inverse(&input));
+        assert_eq!(first.apply_bits_inverse(&first.apply_bits(&input)), input);
+
+        let blocks = [[1, 2], [3, 4], [5, 6]];
+        let permutation = super::Permutation::new([1, 2, 0]).unwrap();
+        assert_eq!(permutation.apply_blocks(&blocks), [[3, 4], [5, 6], [1, 2]]);
+        assert_eq!(permutation.apply_blocks_inverse(&permutation.apply_blocks(&blocks)), blocks);
+        assert_eq!(permutation.inverse().apply_blocks(&blocks), permutation.apply_blocks_inverse(&blocks));
+    }
+
+    #[test]
+    fn test_permutation_partial_byte() {
+        // 12 bits take 2 bytes, the low nibble of the second one is padding.
+        let input = [0b1010_0000, 0b0011_1111];
+        let reverse = super::Permutation::new([11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0]).unwrap();
+        assert_eq!(reverse.apply_bits(&input), [0b1100_0000, 0b0101_0000]);
+        assert_eq!(reverse.apply_bits_inverse(&input), [0b1100_0000, 0b0101_0000]);
+        let permutation = super::Permutation::<12>::from_seed_bytes(b"partial");
+        assert_eq!(permutation.apply_bits_inverse(&permutation.apply_bits(&input)), [0b1010_0000, 0b0011_0000]);
+        assert_eq!(permutation.apply_bits(&permutation.apply_bits_inverse(&input)), [0b1010_0000, 0b0011_0000]);
+    }
+
+    #[test]
+    fn test_permutation_cycles() {
+        let permutation = super::Permutation::new([1, 2, 0, 3, 5, 4]).unwrap();
+        assert_eq!(permutation.cycles(), [vec![0, 1, 2], vec![3], vec![4, 5]]);
+        assert_eq!(super::Permutation::<4>::identity().cycles().len(), 4);
+        let random = super::Permutation::<32>::from_seed_bytes(b"cycles");
+        assert_eq!(random.cycles().iter().map(Vec::len).sum::<usize>(), 32);
+    }
+
+    #[cfg(feature = "lehmer")]
+    #[test]
+    fn test_permutation_lehmer() {
+        let permutation = super::Permutation::<16>::from_seed_bytes(b"lehmer");
+        assert_eq!(super::Permutation::from_lehmer(&permutation.to_lehmer()), Ok(permutation));
+        assert_eq!(super::Permutation::<16>::from_lehmer(&BigUint::zero()), Ok(super::Permutation::identity()));
+        let factorial: BigUint = (1..=16usize).map(BigUint::from).product();
+        assert_eq!(super::Permutation::<16>::from_lehmer(&(factorial.clone() - 1usize)).unwrap().as_array(), &[15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0]);
+        assert_eq!(super::Permutation::<16>::from_lehmer(&factorial), Err(Error::InvalidFormat("Lehmer code out of range")));
+    }
+
+    #[test]
+    fn test_permutation_from_seed_bytes() {
+        let permutation = super::Permutation::<32>::from_seed_bytes(b"seed");
+        assert_eq!(permutation, super::Permutation::from_seed_bytes(b"seed"));
+        assert_ne!(permutation, super::Permutation::from_seed_bytes(b"other seed"));
+        let mut sorted = *permutation.as_array();
+        sorted.sort_unstable();
+        assert_eq!(sorted, *super::Permutation::<32>::identity().as_array());
+    }
+
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    #[should_panic(expected = "cannot draw an index below")]
+    fn test_uniform_index_rejects_wide_bounds() {
+        super::uniform_index(|| 0, (1 << 32) + 1);
+    }
+
     #[test]
     fn test_generate_permutation() {
-        let no_perm = super::generate_permutation::<16>(0usize.into());
-        assert_eq!(no_perm, [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]);
-        let perm = super::generate_permutation::<16>(1usize.into());
-        assert_eq!(perm, [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 15, 14]);
-        let perm = super::generate_permutation::<16>(2usize.into());
-        assert_eq!(perm, [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 14, 13, 15]);
+        let no_perm = super::generate_permutation::<16>(&[]);
+        assert_eq!(no_perm, Ok([0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]));
+        let perm = super::generate_permutation::<16>(&[1]);
+        assert_eq!(perm, Ok([0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 15, 14]));
+        let perm = super::generate_permutation::<16>(&[0, 2]);
+        assert_eq!(perm, Ok([0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 14, 13, 15]));
+        // 4! = 24 is one past the largest Lehmer code of 4 elements.
+        assert_eq!(super::generate_permutation::<4>(&[23]), Ok([3, 2, 1, 0]));
+        assert_eq!(super::generate_permutation::<4>(&[24]), Err(Error::InvalidFormat("Lehmer code out of range")));
     }
 
+    #[cfg(feature = "lehmer")]
     #[test]
     fn test_get_permutation_number() {
         let perm = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15];
@@ -157,32 +688,32 @@ mod tests {
     #[test]
     fn test_permute_block_set() {
         let blocks = [
-            &[0b0000_0001, 0b0000_0010],
-            &[0b0000_0011, 0b0000_0100],
-            &[0b0000_0101, 0b0000_0110],
+            [0b0000_0001, 0b0000_0010],
+            [0b0000_0011, 0b0000_0100],
+            [0b0000_0101, 0b0000_0110],
         ];
         let permutation_key = [1, 2, 0];
         let output = super::permute_block_set(&blocks, &permutation_key);
         assert_eq!(output, [
-            &[0b0000_0011, 0b0000_0100],
-            &[0b0000_0101, 0b0000_0110],
-            &[0b0000_0001, 0b0000_0010],
+            [0b0000_0011, 0b0000_0100],
+            [0b0000_0101, 0b0000_0110],
+            [0b0000_0001, 0b0000_0010],
         ]);
     }
 
     #[test]
     fn test_depermute_block_set() {
         let blocks = [
-            &[0b0000_0011, 0b0000_0100],
-            &[0b0000_0101, 0b0000_0110],
-            &[0b0000_0001, 0b0000_0010],
+            [0b0000_0011, 0b0000_0100],
+            [0b0000_0101, 0b0000_0110],
+            [0b0000_0001, 0b0000_0010],
         ];
         let permutation_key = [1, 2, 0];
         let output = super::depermute_block_set(&blocks, &permutation_key);
         assert_eq!(output, [
-            &[0b0000_0001, 0b0000_0010],
-            &[0b0000_0011, 0b0000_0100],
-            &[0b0000_0101, 0b0000_0110],
+            [0b0000_0001, 0b0000_0010],
+            [0b0000_0011, 0b0000_0100],
+            [0b0000_0101, 0b0000_0110],
         ]);
     }
 }
\ No newline at end of file
@@ -0,0 +1,208 @@
+use crate::error::Error;
+use crate::{Envelope, Fingerprint, KeyStore, ReEncryptionKey};
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::net::TcpListener;
+#[cfg(unix)]
+use std::os::unix::net::UnixListener;
+use std::sync::Arc;
+use std::thread;
+
+const MAX_FRAME_LENGTH: usize = 1 << 16;
+const STATUS_OK: u8 = 0;
+const STATUS_ERROR: u8 = 1;
+
+#[derive(Debug, Clone, Default)]
+pub struct Proxy {
+    re_encryption_keys: HashMap<(Fingerprint, Fingerprint), ReEncryptionKey>,
+}
+
+impl Proxy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_store<S: KeyStore + ?Sized>(store: &S) -> Result<Self, Error> {
+        let mut proxy = Self::new();
+        for id in store.list_re_encryption_keys()? {
+            if let Some(re_encryption_key) = store.get_re_encryption_key(&id)? {
+                proxy.insert(re_encryption_key);
+            }
+        }
+        Ok(proxy)
+    }
+
+    pub fn insert(&mut self, re_encryption_key: ReEncryptionKey) {
+        let id = (re_encryption_key.source_fingerprint(), re_encryption_key.target_fingerprint());
+        self.re_encryption_keys.insert(id, re_encryption_key);
+    }
+
+    pub fn re_encrypt(&self, envelope: &Envelope, target: Fingerprint) -> Result<Envelope, Error> {
+        let source = envelope.key_fingerprint();
+        let re_encryption_key = self.re_encryption_keys.get(&(source, target)).ok_or(Error::UnknownReEncryptionKey { source, target })?;
+        envelope.re_encrypt(re_encryption_key)
+    }
+
+    fn handle_request(&self, request: &[u8]) -> Result<Vec<u8>, Error> {
+        if request.len() < 8 {
+            return Err(Error::InvalidFormat("truncated input"));
+        }
+        let (target, envelope) = request.split_at(8);
+        let envelope = Envelope::from_bytes(envelope)?;
+        Ok(self.re_encrypt(&envelope, target.try_into().unwrap())?.to_bytes())
+    }
+
+    pub fn handle_connection<S: Read + Write>(&self, mut stream: S) -> io::Result<()> {
+        while let Some(request) = read_frame(&mut stream)? {
+            let response = match self.handle_request(&request) {
+                Ok(envelope) => [&[STATUS_OK], envelope.as_slice()].concat(),
+                Err(e) => [&[STATUS_ERROR], e.to_string().as_bytes()].concat(),
+            };
+            write_frame(&mut stream, &response)?;
+        }
+        Ok(())
+    }
+
+    pub fn serve_tcp(self: Arc<Self>, listener: TcpListener) -> io::Result<()> {
+        self.serve(listener.incoming())
+    }
+
+    #[cfg(unix)]
+    pub fn serve_unix(self: Arc<Self>, listener: UnixListener) -> io::Result<()> {
+        self.serve(listener.incoming())
+    }
+
+    // A failed accept (e.g. EMFILE or ECONNABORTED) or connection only loses that connection, never the proxy.
+    fn serve<S: Read + Write + Send + 'static>(self: Arc<Self>, incoming: impl Iterator<Item = io::Result<S>>) -> io::Result<()> {
+        for stream in incoming {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    eprintln!("cannot accept connection: {}", e);
+                    continue;
+                }
+            };
+            let proxy = Arc::clone(&self);
+            thread::spawn(move || {
+                if let Err(e) = proxy.handle_connection(stream) {
+                    eprintln!("connection failed: {}", e);
+                }
+            });
+        }
+        Ok(())
+    }
+}
+
+pub fn request_re_encryption<S: Read + Write>(mut stream: S, envelope: &Envelope, target: Fingerprint) -> io::Result<Envelope> {
+    write_frame(&mut stream, &[target.as_slice(), &envelope.to_bytes()].concat())?;
+    let response = read_frame(&mut stream)?.ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+    match response.split_first() {
+        Some((&STATUS_OK, envelope)) => Envelope::from_bytes(envelope).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+        Some((_, message)) => Err(io::Error::other(String::from_utf8_lossy(message).into_owned())),
+        None => Err(io::Error::new(io::ErrorKind::InvalidData, "empty response")),
+    }
+}
+
+fn read_frame<S: Read>(stream: &mut S) -> io::Result<Option<Vec<u8>>> {
+    let mut length = [0u8; 4];
+    match stream.read_exact(&mut length) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let length = u32::from_be_bytes(length) as usize;
+    if length > MAX_FRAME_LENGTH {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "frame too long"));
+    }
+    let mut frame = vec![0u8; length];
+    stream.read_exact(&mut frame)?;
+    Ok(Some(frame))
+}
+
+fn write_frame<S: Write>(stream: &mut S, frame: &[u8]) -> io::Result<()> {
+    stream.write_all(&(frame.len() as u32).to_be_bytes())?;
+    stream.write_all(frame)?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{request_re_encryption, Proxy};
+    use crate::{decrypt, encrypt, Envelope, FileKeyStore, Key, KeyStore, ReEncryptionKey};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::Arc;
+    use std::thread;
+
+    fn proxy_dir(re_encryption_key: &ReEncryptionKey) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = FileKeyStore::open(dir.path()).unwrap();
+        store.put_re_encryption_key("alice-to-bob", re_encryption_key).unwrap();
+        store.put_key("alice", &Key::generate()).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_tcp_proxy() {
+        let message = b"les sanglots longs des violons !";
+        let key1 = Key::generate();
+        let key2 = Key::generate();
+        let dir = proxy_dir(&ReEncryptionKey::generate(&key1, &key2).unwrap());
+        let proxy = Arc::new(Proxy::from_store(&FileKeyStore::open(dir.path()).unwrap()).unwrap());
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || proxy.serve_tcp(listener));
+
+        let envelope = Envelope::new(key1.fingerprint(), 0, encrypt(message, &key1));
+        let mut stream = TcpStream::connect(address).unwrap();
+        let re_encrypted = request_re_encryption(&mut stream, &envelope, key2.fingerprint()).unwrap();
+        assert_eq!(re_encrypted.key_fingerprint(), key2.fingerprint());
+        assert_eq!(message, &decrypt(re_encrypted.ciphertext(), &key2));
+
+        let error = request_re_encryption(&mut stream, &re_encrypted, key1.fingerprint()).unwrap_err();
+        assert!(error.to_string().starts_with("no re-encryption key"));
+    }
+
+    #[test]
+    fn test_proxy_survives_failed_connections() {
+        use std::io::{self, Write};
+
+        let message = b"les sanglots longs des violons !";
+        let key1 = Key::generate();
+        let key2 = Key::generate();
+        let mut proxy = Proxy::new();
+        proxy.insert(ReEncryptionKey::generate(&key1, &key2).unwrap());
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let mut broken = TcpStream::connect(address).unwrap();
+        let (broken_server_side, _) = listener.accept().unwrap();
+        let client = TcpStream::connect(address).unwrap();
+        let (server_side, _) = listener.accept().unwrap();
+        broken.write_all(&[0xff; 4]).unwrap();
+        let incoming = vec![Err(io::Error::from(io::ErrorKind::ConnectionAborted)), Ok(broken_server_side), Ok(server_side)];
+        thread::spawn(move || Arc::new(proxy).serve(incoming.into_iter()));
+
+        let envelope = Envelope::new(key1.fingerprint(), 0, encrypt(message, &key1));
+        let re_encrypted = request_re_encryption(client, &envelope, key2.fingerprint()).unwrap();
+        assert_eq!(message, &decrypt(re_encrypted.ciphertext(), &key2));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unix_proxy() {
+        use std::os::unix::net::{UnixListener, UnixStream};
+
+        let message = b"les sanglots longs des violons !";
+        let key1 = Key::generate();
+        let key2 = Key::generate();
+        let dir = proxy_dir(&ReEncryptionKey::generate(&key1, &key2).unwrap());
+        let proxy = Arc::new(Proxy::from_store(&FileKeyStore::open(dir.path()).unwrap()).unwrap());
+        let socket_path = dir.path().join("proxy.sock");
+        let listener = UnixListener::bind(&socket_path).unwrap();
+        thread::spawn(move || proxy.serve_unix(listener));
+
+        let envelope = Envelope::new(key1.fingerprint(), 0, encrypt(message, &key1));
+        let stream = UnixStream::connect(&socket_path).unwrap();
+        let re_encrypted = request_re_encryption(stream, &envelope, key2.fingerprint()).unwrap();
+        assert_eq!(message, &decrypt(re_encrypted.ciphertext(), &key2));
+    }
+}
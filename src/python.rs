@@ -0,0 +1,105 @@
+use crate::error::Error;
+use crate::{Encrypted, Key, Plaintext, ReEncryptionKey};
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+create_exception!(symmetric_pre, SymmetricPreError, PyException);
+create_exception!(symmetric_pre, InvalidFormatError, SymmetricPreError);
+create_exception!(symmetric_pre, KeyMismatchError, SymmetricPreError);
+create_exception!(symmetric_pre, IntegrityCheckError, SymmetricPreError);
+
+impl From<Error> for PyErr {
+    fn from(error: Error) -> Self {
+        let message = error.to_string();
+        match error {
+            Error::InvalidFormat(_) => InvalidFormatError::new_err(message),
+            Error::KeyMismatch { .. } => KeyMismatchError::new_err(message),
+            Error::IntegrityCheckFailed => IntegrityCheckError::new_err(message),
+            _ => SymmetricPreError::new_err(message),
+        }
+    }
+}
+
+#[pyclass(name = "Key", frozen)]
+pub struct PyKey(Key);
+
+#[pymethods]
+impl PyKey {
+    #[staticmethod]
+    fn generate() -> Self {
+        PyKey(Key::generate())
+    }
+
+    #[staticmethod]
+    fn from_bytes(bytes: &[u8]) -> PyResult<Self> {
+        Ok(PyKey(Key::from_bytes(bytes)?))
+    }
+
+    fn to_bytes<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.0.to_bytes())
+    }
+
+    fn fingerprint<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.0.fingerprint())
+    }
+}
+
+#[pyclass(name = "ReEncryptionKey", frozen)]
+pub struct PyReEncryptionKey(ReEncryptionKey);
+
+#[pymethods]
+impl PyReEncryptionKey {
+    #[staticmethod]
+    fn generate(old_key: &PyKey, new_key: &PyKey) -> PyResult<Self> {
+        Ok(PyReEncryptionKey(ReEncryptionKey::generate(&old_key.0, &new_key.0)?))
+    }
+
+    #[staticmethod]
+    fn from_bytes(bytes: &[u8]) -> PyResult<Self> {
+        Ok(PyReEncryptionKey(ReEncryptionKey::from_bytes(bytes)?))
+    }
+
+    fn to_bytes<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.0.to_bytes())
+    }
+}
+
+#[pyfunction]
+fn encrypt<'py>(py: Python<'py>, message: &[u8], key: &PyKey) -> PyResult<Bound<'py, PyBytes>> {
+    let message: &Plaintext = message
+        .try_into()
+        .map_err(|_| PyValueError::new_err(format!("message must be {} bytes long", size_of::<Plaintext>())))?;
+    Ok(PyBytes::new(py, &crate::encrypt(message, &key.0)))
+}
+
+#[pyfunction]
+fn decrypt<'py>(py: Python<'py>, encrypted: &[u8], key: &PyKey) -> PyResult<Bound<'py, PyBytes>> {
+    Ok(PyBytes::new(py, &crate::decrypt(ciphertext(encrypted)?, &key.0)))
+}
+
+#[pyfunction]
+fn re_encrypt<'py>(py: Python<'py>, encrypted: &[u8], re_encryption_key: &PyReEncryptionKey) -> PyResult<Bound<'py, PyBytes>> {
+    Ok(PyBytes::new(py, &crate::re_encrypt(ciphertext(encrypted)?, &re_encryption_key.0)))
+}
+
+fn ciphertext(encrypted: &[u8]) -> PyResult<&Encrypted> {
+    encrypted
+        .try_into()
+        .map_err(|_| PyValueError::new_err(format!("ciphertext must be {} bytes long", size_of::<Encrypted>())))
+}
+
+#[pymodule]
+pub fn symmetric_pre(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyKey>()?;
+    m.add_class::<PyReEncryptionKey>()?;
+    m.add_function(wrap_pyfunction!(encrypt, m)?)?;
+    m.add_function(wrap_pyfunction!(decrypt, m)?)?;
+    m.add_function(wrap_pyfunction!(re_encrypt, m)?)?;
+    m.add("SymmetricPreError", m.py().get_type::<SymmetricPreError>())?;
+    m.add("InvalidFormatError", m.py().get_type::<InvalidFormatError>())?;
+    m.add("KeyMismatchError", m.py().get_type::<KeyMismatchError>())?;
+    m.add("IntegrityCheckError", m.py().get_type::<IntegrityCheckError>())?;
+    Ok(())
+}
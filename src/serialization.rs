@@ -0,0 +1,132 @@
+use crate::error::Error;
+use crate::{BLOCK_SIZE_BITS, MESSAGE_BLOCKS_COUNT};
+use alloc::vec::Vec;
+#[cfg(feature = "lehmer")]
+use num_bigint::BigUint;
+
+pub(crate) const FORMAT_VERSION: u8 = 4;
+// Version 1 stored the permutations of keys and re-encryption keys as their Lehmer code, every other format is unchanged.
+pub(crate) const LEHMER_FORMAT_VERSION: u8 = 1;
+// Envelopes record their AONT kind since version 3, older ones all used the hash-and-XOR AONT.
+pub(crate) const AONT_FORMAT_VERSION: u8 = 3;
+// Keys, envelopes, multi-envelopes (per recipient) and wrapped keys record their AONT hash since version 4, older ones
+// all used SHA3-256. Multi-envelopes also record their AONT kind since version 4, older ones all used hash-and-XOR.
+pub(crate) const HASH_FORMAT_VERSION: u8 = 4;
+// Sealed objects authenticate their header and nonce along with their payload since version 4.
+#[cfg(feature = "std")]
+pub(crate) const ASSOCIATED_DATA_FORMAT_VERSION: u8 = 4;
+
+pub(crate) fn write_header(output: &mut Vec<u8>, magic: &[u8; 4]) {
+    output.extend_from_slice(magic);
+    output.push(FORMAT_VERSION);
+}
+
+pub(crate) fn write_scheme_parameters(output: &mut Vec<u8>) {
+    output.extend_from_slice(&(BLOCK_SIZE_BITS as u16).to_be_bytes());
+    output.extend_from_slice(&(MESSAGE_BLOCKS_COUNT as u16).to_be_bytes());
+}
+
+#[cfg(feature = "lehmer")]
+pub(crate) fn write_lehmer_header(output: &mut Vec<u8>, magic: &[u8; 4]) {
+    output.extend_from_slice(magic);
+    output.push(LEHMER_FORMAT_VERSION);
+}
+
+pub(crate) fn write_permutation<const ELEMENTS_COUNT: usize>(output: &mut Vec<u8>, permutation: &[usize; ELEMENTS_COUNT]) {
+    for &index in permutation {
+        if ELEMENTS_COUNT <= 1 << 8 {
+            output.push(index as u8);
+        } else {
+            output.extend_from_slice(&(index as u16).to_be_bytes());
+        }
+    }
+}
+
+#[cfg(feature = "lehmer")]
+pub(crate) fn write_biguint(output: &mut Vec<u8>, n: &BigUint) {
+    let bytes = n.to_bytes_be();
+    output.extend_from_slice(&(bytes.len() as u16).to_be_bytes());
+    output.extend_from_slice(&bytes);
+}
+
+pub(crate) struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    pub(crate) fn read_header(&mut self, magic: &[u8; 4]) -> Result<u8, Error> {
+        if self.read_array::<4>()? != *magic {
+            return Err(Error::InvalidFormat("unexpected magic bytes"));
+        }
+        let version = self.read_u8()?;
+        if !(LEHMER_FORMAT_VERSION..=FORMAT_VERSION).contains(&version) {
+            return Err(Error::InvalidFormat("unsupported format version"));
+        }
+        Ok(version)
+    }
+
+    pub(crate) fn read_scheme_parameters(&mut self) -> Result<(), Error> {
+        if self.read_u16()? as usize != BLOCK_SIZE_BITS || self.read_u16()? as usize != MESSAGE_BLOCKS_COUNT {
+            return Err(Error::InvalidFormat("unsupported scheme parameters"));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn read_slice(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if self.bytes.len() < len {
+            return Err(Error::InvalidFormat("truncated input"));
+        }
+        let (slice, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(slice)
+    }
+
+    pub(crate) fn read_array<const LEN: usize>(&mut self) -> Result<[u8; LEN], Error> {
+        Ok(self.read_slice(LEN)?.try_into().unwrap())
+    }
+
+    pub(crate) fn read_u8(&mut self) -> Result<u8, Error> {
+        Ok(self.read_array::<1>()?[0])
+    }
+
+    pub(crate) fn read_u16(&mut self) -> Result<u16, Error> {
+        Ok(u16::from_be_bytes(self.read_array()?))
+    }
+
+    pub(crate) fn read_u64(&mut self) -> Result<u64, Error> {
+        Ok(u64::from_be_bytes(self.read_array()?))
+    }
+
+    pub(crate) fn read_permutation<const ELEMENTS_COUNT: usize>(&mut self) -> Result<[usize; ELEMENTS_COUNT], Error> {
+        let mut permutation = [0usize; ELEMENTS_COUNT];
+        for index in permutation.iter_mut() {
+            *index = if ELEMENTS_COUNT <= 1 << 8 {
+                self.read_u8()? as usize
+            } else {
+                self.read_u16()? as usize
+            };
+        }
+        Ok(permutation)
+    }
+
+    pub(crate) fn read_biguint_bytes(&mut self) -> Result<&'a [u8], Error> {
+        let len = self.read_u16()? as usize;
+        self.read_slice(len)
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) fn read_remaining(&mut self) -> &'a [u8] {
+        core::mem::take(&mut self.bytes)
+    }
+
+    pub(crate) fn finish(self) -> Result<(), Error> {
+        if !self.bytes.is_empty() {
+            return Err(Error::InvalidFormat("trailing bytes"));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,132 @@
+use crate::error::Error;
+use crate::{encrypt, Envelope, Key, Plaintext, ReEncryptionKey, UpdateToken};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EpochKey {
+    epoch: u64,
+    key: Key,
+}
+
+impl EpochKey {
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    pub fn key(&self) -> &Key {
+        &self.key
+    }
+}
+
+pub fn key_gen() -> EpochKey {
+    EpochKey { epoch: 0, key: Key::generate() }
+}
+
+pub fn next_key_gen(current_key: &EpochKey) -> EpochKey {
+    EpochKey { epoch: current_key.epoch + 1, key: Key::generate().with_aont_hash(current_key.key.aont_hash()) }
+}
+
+pub fn enc(key: &EpochKey, message: &Plaintext) -> Envelope {
+    Envelope::new(key.key.fingerprint(), key.epoch, encrypt(message, &key.key)).with_aont_hash(key.key.aont_hash())
+}
+
+pub fn dec(key: &EpochKey, envelope: &Envelope) -> Result<Plaintext, Error> {
+    if envelope.epoch() != key.epoch {
+        return Err(Error::EpochMismatch { expected: key.epoch, found: envelope.epoch() });
+    }
+    envelope.decrypt(&key.key)
+}
+
+pub fn token_gen(key: &EpochKey, next_key: &EpochKey) -> Result<UpdateToken, Error> {
+    if next_key.epoch != key.epoch + 1 {
+        return Err(Error::EpochMismatch { expected: key.epoch + 1, found: next_key.epoch });
+    }
+    Ok(UpdateToken::new(key.epoch, next_key.epoch, ReEncryptionKey::generate(&key.key, &next_key.key)?))
+}
+
+pub fn upd(token: &UpdateToken, envelope: &Envelope) -> Result<Envelope, Error> {
+    token.apply(envelope)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dec, enc, key_gen, next_key_gen, token_gen, upd};
+    use crate::error::Error;
+    use crate::{re_encrypt, Envelope};
+
+    #[test]
+    fn test_correctness() {
+        let message = b"les sanglots longs des violons !";
+        let mut key = key_gen();
+        let mut envelope = enc(&key, message);
+        for epoch in 1..=4 {
+            let next_key = next_key_gen(&key);
+            let token = token_gen(&key, &next_key).unwrap();
+            envelope = upd(&token, &envelope).unwrap();
+            key = next_key;
+            assert_eq!(envelope.epoch(), epoch);
+            assert_eq!(message, &dec(&key, &envelope).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_epoch_tracking() {
+        let key = key_gen();
+        let next_key = next_key_gen(&key);
+        let envelope = enc(&key, b"les sanglots longs des violons !");
+        assert_eq!(dec(&next_key, &envelope), Err(Error::EpochMismatch { expected: 1, found: 0 }));
+        assert_eq!(token_gen(&key, &key), Err(Error::EpochMismatch { expected: 1, found: 0 }));
+        let token = token_gen(&key, &next_key).unwrap();
+        let updated = upd(&token, &envelope).unwrap();
+        assert_eq!(upd(&token, &updated), Err(Error::EpochMismatch { expected: 0, found: 1 }));
+    }
+
+    #[test]
+    fn test_randomized_encryption() {
+        let message = b"les sanglots longs des violons !";
+        let key = key_gen();
+        assert_ne!(enc(&key, message), enc(&key, message));
+    }
+
+    #[test]
+    fn test_deterministic_update_is_linkable() {
+        // Upd is deterministic, so whoever holds the token links an old ciphertext to its update:
+        // the scheme does not provide unlinkability once a token leaks.
+        let message = b"les sanglots longs des violons !";
+        let key = key_gen();
+        let next_key = next_key_gen(&key);
+        let token = token_gen(&key, &next_key).unwrap();
+        let envelope = enc(&key, message);
+        let other_envelope = enc(&key, message);
+        let updated = upd(&token, &envelope).unwrap();
+        assert_eq!(upd(&token, &envelope).unwrap(), updated);
+        assert_ne!(upd(&token, &other_envelope).unwrap(), updated);
+    }
+
+    #[test]
+    fn test_update_linking_needs_the_token() {
+        // Without the token, nothing public ties an old ciphertext to its update: the update depends on the secret
+        // permutations the token is made of, and a token between other keys of the same epochs updates the same
+        // ciphertext to a different one. Linking without the token therefore amounts to recovering the permutations,
+        // i.e. it is only as hard as the permutations are secret, which this test cannot measure.
+        let message = b"les sanglots longs des violons !";
+        let key = key_gen();
+        let token = token_gen(&key, &next_key_gen(&key)).unwrap();
+        let other_token = token_gen(&key, &next_key_gen(&key)).unwrap();
+        let envelope = enc(&key, message);
+        let updated = upd(&token, &envelope).unwrap();
+        assert_ne!(updated.ciphertext(), envelope.ciphertext());
+        assert_ne!(upd(&other_token, &envelope).unwrap().ciphertext(), updated.ciphertext());
+    }
+
+    #[test]
+    fn test_bidirectional_update() {
+        // Tokens can be inverted, so an updated ciphertext can be downgraded to the previous epoch.
+        let message = b"les sanglots longs des violons !";
+        let key = key_gen();
+        let next_key = next_key_gen(&key);
+        let token = token_gen(&key, &next_key).unwrap();
+        let fresh = enc(&next_key, message);
+        let downgraded = Envelope::new(key.key().fingerprint(), key.epoch(), re_encrypt(fresh.ciphertext(), &token.re_encryption_key().inverse()));
+        assert_eq!(message, &dec(&key, &downgraded).unwrap());
+    }
+}
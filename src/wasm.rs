@@ -0,0 +1,66 @@
+use crate::{Encrypted, Key, Plaintext, ReEncryptionKey};
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen(js_name = Key)]
+pub struct WasmKey(Key);
+
+#[wasm_bindgen(js_class = Key)]
+impl WasmKey {
+    pub fn generate() -> WasmKey {
+        WasmKey(Key::generate())
+    }
+
+    #[wasm_bindgen(js_name = fromBytes)]
+    pub fn from_bytes(bytes: &[u8]) -> Result<WasmKey, JsError> {
+        Ok(WasmKey(Key::from_bytes(bytes)?))
+    }
+
+    #[wasm_bindgen(js_name = toBytes)]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.0.to_bytes()
+    }
+
+    pub fn fingerprint(&self) -> Vec<u8> {
+        self.0.fingerprint().to_vec()
+    }
+}
+
+#[wasm_bindgen(js_name = ReEncryptionKey)]
+pub struct WasmReEncryptionKey(ReEncryptionKey);
+
+#[wasm_bindgen(js_class = ReEncryptionKey)]
+impl WasmReEncryptionKey {
+    pub fn generate(old_key: &WasmKey, new_key: &WasmKey) -> Result<WasmReEncryptionKey, JsError> {
+        Ok(WasmReEncryptionKey(ReEncryptionKey::generate(&old_key.0, &new_key.0)?))
+    }
+
+    #[wasm_bindgen(js_name = fromBytes)]
+    pub fn from_bytes(bytes: &[u8]) -> Result<WasmReEncryptionKey, JsError> {
+        Ok(WasmReEncryptionKey(ReEncryptionKey::from_bytes(bytes)?))
+    }
+
+    #[wasm_bindgen(js_name = toBytes)]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.0.to_bytes()
+    }
+}
+
+#[wasm_bindgen]
+pub fn encrypt(message: &[u8], key: &WasmKey) -> Result<Vec<u8>, JsError> {
+    let message: &Plaintext = message.try_into().map_err(|_| JsError::new(&format!("message must be {} bytes long", size_of::<Plaintext>())))?;
+    Ok(crate::encrypt(message, &key.0).to_vec())
+}
+
+#[wasm_bindgen]
+pub fn decrypt(encrypted: &[u8], key: &WasmKey) -> Result<Vec<u8>, JsError> {
+    Ok(crate::decrypt(ciphertext(encrypted)?, &key.0).to_vec())
+}
+
+#[wasm_bindgen(js_name = reEncrypt)]
+pub fn re_encrypt(encrypted: &[u8], re_encryption_key: &WasmReEncryptionKey) -> Result<Vec<u8>, JsError> {
+    Ok(crate::re_encrypt(ciphertext(encrypted)?, &re_encryption_key.0).to_vec())
+}
+
+fn ciphertext(encrypted: &[u8]) -> Result<&Encrypted, JsError> {
+    encrypted.try_into().map_err(|_| JsError::new(&format!("ciphertext must be {} bytes long", size_of::<Encrypted>())))
+}
@@ -0,0 +1,153 @@
+use crate::error::Error;
+use crate::serialization::{write_header, Reader, HASH_FORMAT_VERSION};
+use crate::{encrypt, re_encrypt, AontHash, Encrypted, Fingerprint, Key, Plaintext, ReEncryptionKey, Scheme};
+use sha3::{Digest, Sha3_256};
+
+pub(crate) const WRAPPED_KEY_MAGIC: &[u8; 4] = b"SPWK";
+const INTEGRITY_LENGTH: usize = 8;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WrappedKey {
+    kek_fingerprint: Fingerprint,
+    aont_hash: AontHash,
+    blocks: Vec<Encrypted>,
+}
+
+impl Key {
+    pub fn wrap(&self, kek: &Key) -> WrappedKey {
+        let key_bytes = self.to_bytes();
+        let mut framed = Vec::new();
+        framed.extend_from_slice(&(key_bytes.len() as u32).to_be_bytes());
+        framed.extend_from_slice(&key_bytes);
+        framed.extend_from_slice(&integrity(&key_bytes));
+        framed.resize(framed.len().div_ceil(size_of::<Plaintext>()) * size_of::<Plaintext>(), 0);
+        let blocks = framed
+            .chunks_exact(size_of::<Plaintext>())
+            .map(|chunk| encrypt(chunk.try_into().unwrap(), kek))
+            .collect();
+        WrappedKey { kek_fingerprint: kek.fingerprint(), aont_hash: kek.aont_hash(), blocks }
+    }
+}
+
+impl WrappedKey {
+    pub fn kek_fingerprint(&self) -> Fingerprint {
+        self.kek_fingerprint
+    }
+
+    pub fn aont_hash(&self) -> AontHash {
+        self.aont_hash
+    }
+
+    pub fn unwrap(&self, kek: &Key) -> Result<Key, Error> {
+        if kek.fingerprint() != self.kek_fingerprint {
+            return Err(Error::KeyMismatch { expected: self.kek_fingerprint, found: kek.fingerprint() });
+        }
+        let framed: Vec<u8> = self.blocks.iter().flat_map(|block| Scheme::new().decrypt_with_hash(block, kek, self.aont_hash)).collect();
+        let mut reader = Reader::new(&framed);
+        let length = u32::from_be_bytes(reader.read_array()?) as usize;
+        let key_bytes = reader.read_slice(length).map_err(|_| Error::IntegrityCheckFailed)?;
+        let expected_integrity = reader.read_array::<INTEGRITY_LENGTH>().map_err(|_| Error::IntegrityCheckFailed)?;
+        if integrity(key_bytes) != expected_integrity || reader.read_remaining().iter().any(|&b| b != 0) {
+            return Err(Error::IntegrityCheckFailed);
+        }
+        Key::from_bytes(key_bytes)
+    }
+
+    pub fn re_encrypt(&self, re_encryption_key: &ReEncryptionKey) -> Result<WrappedKey, Error> {
+        if self.kek_fingerprint != re_encryption_key.source_fingerprint() {
+            return Err(Error::KeyMismatch { expected: re_encryption_key.source_fingerprint(), found: self.kek_fingerprint });
+        }
+        Ok(WrappedKey {
+            kek_fingerprint: re_encryption_key.target_fingerprint(),
+            aont_hash: self.aont_hash,
+            blocks: self.blocks.iter().map(|block| re_encrypt(block, re_encryption_key)).collect(),
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut output = Vec::new();
+        write_header(&mut output, WRAPPED_KEY_MAGIC);
+        output.extend_from_slice(&self.kek_fingerprint);
+        output.push(self.aont_hash.to_byte());
+        output.extend_from_slice(&(self.blocks.len() as u32).to_be_bytes());
+        for block in &self.blocks {
+            output.extend_from_slice(block);
+        }
+        output
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader::new(bytes);
+        let version = reader.read_header(WRAPPED_KEY_MAGIC)?;
+        let kek_fingerprint = reader.read_array()?;
+        let aont_hash = if version >= HASH_FORMAT_VERSION { AontHash::from_byte(reader.read_u8()?)? } else { AontHash::Sha3_256 };
+        let blocks_count = u32::from_be_bytes(reader.read_array()?);
+        let blocks = (0..blocks_count).map(|_| reader.read_array()).collect::<Result<_, _>>()?;
+        reader.finish()?;
+        Ok(Self { kek_fingerprint, aont_hash, blocks })
+    }
+}
+
+fn integrity(key_bytes: &[u8]) -> [u8; INTEGRITY_LENGTH] {
+    let mut hasher = Sha3_256::new();
+    Digest::update(&mut hasher, key_bytes);
+    hasher.finalize()[..INTEGRITY_LENGTH].try_into().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::Error;
+    use crate::{AontHash, Key, ReEncryptionKey, WrappedKey};
+
+    #[test]
+    fn test_wrap_unwrap() {
+        let document_key = Key::generate();
+        let kek = Key::generate();
+        let wrapped = document_key.wrap(&kek);
+        assert_eq!(wrapped.unwrap(&kek), Ok(document_key));
+        assert_eq!(WrappedKey::from_bytes(&wrapped.to_bytes()), Ok(wrapped.clone()));
+
+        let other_kek = Key::generate();
+        assert_eq!(wrapped.unwrap(&other_kek), Err(Error::KeyMismatch { expected: kek.fingerprint(), found: other_kek.fingerprint() }));
+    }
+
+    #[test]
+    fn test_tampered_wrapped_key() {
+        let kek = Key::generate();
+        let mut wrapped = Key::generate().wrap(&kek);
+        wrapped.blocks.swap(0, 1);
+        assert_eq!(wrapped.unwrap(&kek), Err(Error::IntegrityCheckFailed));
+        wrapped.blocks.swap(0, 1);
+        wrapped.blocks.pop();
+        assert_eq!(wrapped.unwrap(&kek), Err(Error::IntegrityCheckFailed));
+    }
+
+    #[test]
+    fn test_wrapped_key_rotation() {
+        let document_key = Key::generate();
+        let old_kek = Key::generate();
+        let new_kek = Key::generate();
+        let re_encryption_key = ReEncryptionKey::generate(&old_kek, &new_kek).unwrap();
+        let rotated = document_key.wrap(&old_kek).re_encrypt(&re_encryption_key).unwrap();
+        assert_eq!(rotated.kek_fingerprint(), new_kek.fingerprint());
+        assert_eq!(rotated.unwrap(&new_kek), Ok(document_key));
+        assert!(rotated.re_encrypt(&re_encryption_key).is_err());
+    }
+
+    #[test]
+    fn test_wrapped_key_aont_hashes() {
+        let document_key = Key::generate().with_aont_hash(AontHash::Sha256);
+        let old_kek = Key::generate().with_aont_hash(AontHash::Blake3);
+        let new_kek = Key::generate().with_aont_hash(AontHash::Blake3);
+        let wrapped = WrappedKey::from_bytes(&document_key.wrap(&old_kek).to_bytes()).unwrap();
+        assert_eq!(wrapped.aont_hash(), AontHash::Blake3);
+        assert_eq!(wrapped.unwrap(&old_kek), Ok(document_key.clone()));
+
+        let rotated = wrapped.re_encrypt(&ReEncryptionKey::generate(&old_kek, &new_kek).unwrap()).unwrap();
+        let rotated = WrappedKey::from_bytes(&rotated.to_bytes()).unwrap();
+        assert_eq!(rotated.aont_hash(), AontHash::Blake3);
+        assert_eq!(rotated.unwrap(&new_kek), Ok(document_key.clone()));
+        // The wrapped key records the hash it was wrapped with, whichever hash the KEK now asks for.
+        assert_eq!(rotated.unwrap(&new_kek.with_aont_hash(AontHash::Sha3_256)), Ok(document_key));
+    }
+}
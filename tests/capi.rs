@@ -0,0 +1,42 @@
+#![cfg(feature = "capi")]
+
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn test_header_is_up_to_date() {
+    let generated = std::fs::read_to_string(Path::new(env!("OUT_DIR")).join("symmetric_pre.h")).unwrap();
+    let committed = std::fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("include/symmetric_pre.h")).unwrap();
+    assert_eq!(generated, committed, "include/symmetric_pre.h is outdated, copy it from {}", env!("OUT_DIR"));
+}
+
+#[test]
+fn test_c_program() {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let target_dir = manifest_dir.join("target/capi");
+    let output_dir = tempfile::tempdir().unwrap();
+    let executable = output_dir.path().join("capi_test");
+
+    let status = Command::new(env!("CARGO"))
+        .current_dir(manifest_dir)
+        .args(["rustc", "--lib", "--features", "capi", "--crate-type", "staticlib", "--target-dir"])
+        .arg(&target_dir)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let status = Command::new(std::env::var("CC").unwrap_or_else(|_| "cc".to_string()))
+        .arg(manifest_dir.join("tests/capi/test.c"))
+        .arg("-I")
+        .arg(manifest_dir.join("include"))
+        .arg(target_dir.join("debug/libsymmetric_pre.a"))
+        .args(["-lpthread", "-ldl", "-lm", "-o"])
+        .arg(&executable)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let output = Command::new(&executable).output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(output.stdout, b"ok\n");
+}
@@ -0,0 +1,67 @@
+#include <stdio.h>
+#include <string.h>
+
+#include "symmetric_pre.h"
+
+#define CHECK(call)                                                                 \
+    do {                                                                            \
+        SpreError error = (call);                                                   \
+        if (error != SPRE_ERROR_OK) {                                               \
+            fprintf(stderr, "%s failed: %s\n", #call, spre_error_message(error));  \
+            return 1;                                                               \
+        }                                                                           \
+    } while (0)
+
+int main(void) {
+    const uint8_t message[SPRE_PLAINTEXT_LENGTH] = "les sanglots longs des violons !";
+    uint8_t encrypted[SPRE_CIPHERTEXT_LENGTH];
+    uint8_t re_encrypted[SPRE_CIPHERTEXT_LENGTH];
+    uint8_t decrypted[SPRE_PLAINTEXT_LENGTH];
+    SpreKey *key1 = NULL;
+    SpreKey *key2 = NULL;
+    SpreKey *key2_copy = NULL;
+    SpreReEncryptionKey *re_encryption_key = NULL;
+    SpreReEncryptionKey *re_encryption_key_copy = NULL;
+    uint8_t *bytes = NULL;
+    size_t length = 0;
+
+    CHECK(spre_key_generate(&key1));
+    CHECK(spre_key_generate(&key2));
+    CHECK(spre_encrypt(key1, message, encrypted));
+    CHECK(spre_decrypt(key1, encrypted, decrypted));
+    if (memcmp(message, decrypted, SPRE_PLAINTEXT_LENGTH) != 0) {
+        fprintf(stderr, "decryption mismatch\n");
+        return 1;
+    }
+
+    CHECK(spre_re_encryption_key_generate(key1, key2, &re_encryption_key));
+    CHECK(spre_re_encryption_key_to_bytes(re_encryption_key, &bytes, &length));
+    CHECK(spre_re_encryption_key_from_bytes(bytes, length, &re_encryption_key_copy));
+    spre_bytes_free(bytes, length);
+    CHECK(spre_re_encrypt(re_encryption_key_copy, encrypted, re_encrypted));
+
+    CHECK(spre_key_to_bytes(key2, &bytes, &length));
+    CHECK(spre_key_from_bytes(bytes, length, &key2_copy));
+    if (spre_key_from_bytes(bytes, length - 1, &key1) != SPRE_ERROR_INVALID_FORMAT) {
+        fprintf(stderr, "truncated key accepted\n");
+        return 1;
+    }
+    spre_bytes_free(bytes, length);
+    CHECK(spre_decrypt(key2_copy, re_encrypted, decrypted));
+    if (memcmp(message, decrypted, SPRE_PLAINTEXT_LENGTH) != 0) {
+        fprintf(stderr, "re-decryption mismatch\n");
+        return 1;
+    }
+    if (spre_encrypt(NULL, message, encrypted) != SPRE_ERROR_NULL_POINTER) {
+        fprintf(stderr, "null key accepted\n");
+        return 1;
+    }
+
+    spre_re_encryption_key_free(re_encryption_key);
+    spre_re_encryption_key_free(re_encryption_key_copy);
+    spre_key_free(key1);
+    spre_key_free(key2);
+    spre_key_free(key2_copy);
+    printf("ok\n");
+    return 0;
+}
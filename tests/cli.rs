@@ -0,0 +1,60 @@
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+
+fn spawn(store: &Path, args: &[&str], input: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_symmetric_pre"))
+        .arg("--store")
+        .arg(store)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn run(store: &Path, args: &[&str], input: &[u8]) -> Vec<u8> {
+    let output = spawn(store, args, input);
+    assert!(output.status.success());
+    output.stdout
+}
+
+#[test]
+fn test_cli_key_store() {
+    let message = b"les sanglots longs des violons !";
+    let dir = tempfile::tempdir().unwrap();
+    run(dir.path(), &["keygen", "1"], b"");
+    run(dir.path(), &["keygen", "2"], b"");
+    run(dir.path(), &["rekey", "1", "2", "1-to-2"], b"");
+    assert_eq!(b"key 1\nkey 2\nrekey 1-to-2\n", run(dir.path(), &["list"], b"").as_slice());
+
+    let encrypted = run(dir.path(), &["encrypt", "1"], message);
+    assert_eq!(message, run(dir.path(), &["decrypt", "1"], &encrypted).as_slice());
+    let re_encrypted = run(dir.path(), &["re-encrypt", "1-to-2"], &encrypted);
+    assert_eq!(message, run(dir.path(), &["decrypt", "2"], &re_encrypted).as_slice());
+}
+
+#[test]
+fn test_cli_wrong_key() {
+    let message = b"les sanglots longs des violons !";
+    let dir = tempfile::tempdir().unwrap();
+    run(dir.path(), &["keygen", "1"], b"");
+    run(dir.path(), &["keygen", "2"], b"");
+    let encrypted = run(dir.path(), &["encrypt", "1"], message);
+    assert!(!spawn(dir.path(), &["decrypt", "2"], &encrypted).status.success());
+}
+
+#[test]
+fn test_cli_message_length() {
+    let dir = tempfile::tempdir().unwrap();
+    run(dir.path(), &["keygen", "1"], b"");
+    for message in [&b"too short"[..], &[0; 33]] {
+        let output = spawn(dir.path(), &["encrypt", "1"], message);
+        assert!(!output.status.success());
+        let expected = format!("the message must be exactly 32 bytes long, not {}\n", message.len());
+        assert!(String::from_utf8(output.stderr).unwrap().ends_with(&expected));
+    }
+}
@@ -0,0 +1,90 @@
+#![cfg(feature = "http")]
+#![feature(tcp_linger)]
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use symmetric_pre::http::HttpProxy;
+use symmetric_pre::{decrypt, encrypt, Envelope, Key, ReEncryptionKey};
+
+fn start_proxy() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let proxy = Arc::new(HttpProxy::new(listener).unwrap());
+    thread::spawn(move || proxy.serve());
+    address
+}
+
+fn send(address: SocketAddr, method: &str, path: &str, body: &[u8]) -> (u16, String) {
+    let mut stream = TcpStream::connect(address).unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+    write!(stream, "{} {} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", method, path, body.len()).unwrap();
+    stream.write_all(body).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let status = response[9..12].parse().unwrap();
+    let body = response.split_once("\r\n\r\n").unwrap().1.to_string();
+    (status, body)
+}
+
+fn re_encrypt_request(envelope: &Envelope, rekey: &str) -> Vec<u8> {
+    serde_json::json!({ "envelope": hex::encode(envelope.to_bytes()), "rekey": rekey }).to_string().into_bytes()
+}
+
+#[test]
+fn test_http_re_encryption() {
+    let message = b"les sanglots longs des violons !";
+    let key1 = Key::generate();
+    let key2 = Key::generate();
+    let address = start_proxy();
+
+    let (status, _) = send(address, "PUT", "/rekeys/alice-to-bob", &ReEncryptionKey::generate(&key1, &key2).unwrap().to_bytes());
+    assert_eq!(status, 204);
+
+    let envelope = Envelope::new(key1.fingerprint(), 0, encrypt(message, &key1));
+    let (status, body) = send(address, "POST", "/reencrypt", &re_encrypt_request(&envelope, "alice-to-bob"));
+    assert_eq!(status, 200);
+    let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+    let re_encrypted = Envelope::from_bytes(&hex::decode(body["envelope"].as_str().unwrap()).unwrap()).unwrap();
+    assert_eq!(message, &decrypt(re_encrypted.ciphertext(), &key2));
+
+    let (status, _) = send(address, "POST", "/reencrypt", &re_encrypt_request(&re_encrypted, "alice-to-bob"));
+    assert_eq!(status, 400);
+
+    let (status, _) = send(address, "DELETE", "/rekeys/alice-to-bob", b"");
+    assert_eq!(status, 204);
+    let (status, body) = send(address, "POST", "/reencrypt", &re_encrypt_request(&envelope, "alice-to-bob"));
+    assert_eq!(status, 404);
+    assert!(body.contains("unknown re-encryption key"));
+}
+
+#[test]
+fn test_http_invalid_requests() {
+    let address = start_proxy();
+    assert_eq!(send(address, "PUT", "/rekeys/broken", b"not a key").0, 400);
+    let mut not_a_bijection = ReEncryptionKey::generate(&Key::generate(), &Key::generate()).unwrap().to_bytes();
+    not_a_bijection[22] = not_a_bijection[21];
+    assert_eq!(send(address, "PUT", "/rekeys/broken", &not_a_bijection), (400, r#"{"error":"invalid format: not a bijection"}"#.to_string()));
+    let mut out_of_range = ReEncryptionKey::generate(&Key::generate(), &Key::generate()).unwrap().to_bytes();
+    out_of_range[21] = 32;
+    assert_eq!(send(address, "PUT", "/rekeys/broken", &out_of_range), (400, r#"{"error":"invalid format: not a bijection"}"#.to_string()));
+    assert_eq!(send(address, "DELETE", "/rekeys/missing", b"").0, 404);
+    assert_eq!(send(address, "POST", "/reencrypt", b"{}").0, 400);
+    assert_eq!(send(address, "GET", "/reencrypt", b"").0, 404);
+    assert_eq!(send(address, "PUT", "/rekeys/huge", &vec![0; (1 << 16) + 1]), (413, r#"{"error":"body longer than 65536 bytes"}"#.to_string()));
+}
+
+#[test]
+fn test_http_truncated_request() {
+    let address = start_proxy();
+    // Resetting the connection in the middle of the body makes the proxy's read fail rather than end early.
+    let mut stream = TcpStream::connect(address).unwrap();
+    write!(stream, "PUT /rekeys/truncated HTTP/1.1\r\nHost: localhost\r\nContent-Length: 4096\r\n\r\n").unwrap();
+    stream.write_all(&[0; 10]).unwrap();
+    thread::sleep(Duration::from_millis(100));
+    stream.set_linger(Some(Duration::ZERO)).unwrap();
+    drop(stream);
+    assert_eq!(send(address, "DELETE", "/rekeys/missing", b"").0, 404);
+}
@@ -0,0 +1,35 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const TARGET: &str = "thumbv7em-none-eabihf";
+const SKIP_VARIABLE: &str = "SYMMETRIC_PRE_SKIP_NO_STD";
+
+#[test]
+fn test_no_std_build() {
+    let target_libdir = Command::new(std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string()))
+        .args(["--print", "target-libdir", "--target", TARGET])
+        .output()
+        .unwrap();
+    if !target_libdir.status.success() || !Path::new(String::from_utf8_lossy(&target_libdir.stdout).trim()).exists() {
+        assert!(
+            std::env::var_os(SKIP_VARIABLE).is_some(),
+            "{} is not installed: run `rustup target add {}`, or set {}=1 to skip the no_std build",
+            TARGET,
+            TARGET,
+            SKIP_VARIABLE
+        );
+        eprintln!("skipping the no_std build, {} is not installed", TARGET);
+        return;
+    }
+
+    // A directory of its own, since the outer cargo may still hold the lock on its target directory.
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let target_dir = std::env::var_os("CARGO_TARGET_DIR").map_or_else(|| manifest_dir.join("target"), PathBuf::from).join("no_std");
+    let status = Command::new(env!("CARGO"))
+        .current_dir(manifest_dir)
+        .args(["build", "--lib", "--no-default-features", "--features", "analysis", "--target", TARGET, "--target-dir"])
+        .arg(target_dir)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
@@ -0,0 +1,31 @@
+use std::io::{BufRead, BufReader};
+use std::net::TcpStream;
+use std::process::{Command, Stdio};
+use symmetric_pre::proxy::request_re_encryption;
+use symmetric_pre::{decrypt, encrypt, Envelope, Key, ReEncryptionKey};
+
+#[test]
+fn test_proxy_binary() {
+    let message = b"les sanglots longs des violons !";
+    let key1 = Key::generate();
+    let key2 = Key::generate();
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("1-to-2.rekey"), ReEncryptionKey::generate(&key1, &key2).unwrap().to_bytes()).unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_symmetric-pre-proxy"))
+        .arg("--rekeys")
+        .arg(dir.path())
+        .args(["--tcp", "127.0.0.1:0"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut line = String::new();
+    BufReader::new(child.stdout.take().unwrap()).read_line(&mut line).unwrap();
+    let address = line.trim().strip_prefix("listening on ").unwrap().to_string();
+
+    let envelope = Envelope::new(key1.fingerprint(), 0, encrypt(message, &key1));
+    let result = request_re_encryption(TcpStream::connect(address).unwrap(), &envelope, key2.fingerprint());
+    child.kill().unwrap();
+    child.wait().unwrap();
+    assert_eq!(message, &decrypt(result.unwrap().ciphertext(), &key2));
+}
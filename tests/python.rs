@@ -0,0 +1,21 @@
+#![cfg(feature = "python")]
+
+use pyo3::prelude::*;
+use std::ffi::CString;
+use symmetric_pre::python::symmetric_pre;
+
+#[test]
+fn test_python_bindings() {
+    pyo3::append_to_inittab!(symmetric_pre);
+    Python::initialize();
+    let source = std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/python/test_symmetric_pre.py")).unwrap();
+    Python::attach(|py| {
+        let module = PyModule::from_code(py, &CString::new(source).unwrap(), c"test_symmetric_pre.py", c"test_symmetric_pre").unwrap();
+        for (name, test) in module.dict() {
+            let name: String = name.extract().unwrap();
+            if name.starts_with("test_") {
+                test.call0().unwrap_or_else(|e| panic!("{}: {}", name, e));
+            }
+        }
+    });
+}
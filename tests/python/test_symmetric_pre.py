@@ -0,0 +1,47 @@
+import symmetric_pre
+from symmetric_pre import Key, ReEncryptionKey, decrypt, encrypt, re_encrypt
+
+MESSAGE = b"les sanglots longs des violons !"
+
+
+def assert_raises(exception, function, *args):
+    try:
+        function(*args)
+    except exception:
+        return
+    raise AssertionError(f"{function.__name__} did not raise {exception.__name__}")
+
+
+def test_encryption_re_encryption():
+    key1 = Key.generate()
+    key2 = Key.from_bytes(Key.generate().to_bytes())
+    encrypted = encrypt(MESSAGE, key1)
+    assert isinstance(encrypted, bytes)
+    assert decrypt(encrypted, key1) == MESSAGE
+
+    re_encryption_key = ReEncryptionKey.from_bytes(ReEncryptionKey.generate(key1, key2).to_bytes())
+    assert decrypt(re_encrypt(encrypted, re_encryption_key), key2) == MESSAGE
+
+
+def test_fingerprint():
+    key = Key.generate()
+    assert len(key.fingerprint()) == 8
+    assert key.fingerprint() == Key.from_bytes(key.to_bytes()).fingerprint()
+
+
+def test_errors():
+    key = Key.generate()
+    assert_raises(ValueError, encrypt, b"too short", key)
+    assert_raises(ValueError, decrypt, b"too short", key)
+    assert_raises(symmetric_pre.InvalidFormatError, Key.from_bytes, b"not a key")
+    assert_raises(symmetric_pre.SymmetricPreError, ReEncryptionKey.from_bytes, key.to_bytes())
+    not_a_bijection = bytearray(key.to_bytes())
+    not_a_bijection[7] = not_a_bijection[6]
+    assert_raises(symmetric_pre.InvalidFormatError, Key.from_bytes, bytes(not_a_bijection))
+
+
+if __name__ == "__main__":
+    for name, test in list(globals().items()):
+        if name.startswith("test_"):
+            test()
+    print("ok")
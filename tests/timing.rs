@@ -0,0 +1,134 @@
+#![cfg(all(feature = "constant-time", feature = "std"))]
+
+// dudect-style check (Reparaz, Balasch and Verbauwhede, "Dude, is my code constant time?"): time an operation on a
+// fixed secret and on fresh random ones, interleaved in a random order, and run Welch's t-test on both timing
+// distributions after cropping outliers. A |t| above 10 is dudect's threshold for a definite leak.
+
+use rand::seq::SliceRandom;
+use std::hint::black_box;
+use std::time::Instant;
+use symmetric_pre::{encrypt, re_encrypt, Key, Permutation, ReEncryptionKey};
+
+const MEASUREMENTS: usize = 4000;
+const MAX_T: f64 = 10.0;
+// Large enough to spill out of the last level cache, so that the calibration's lookups miss it unless repeated.
+const TABLE_LENGTH: usize = 1 << 27;
+const LOOKUPS: usize = 16;
+const BLOCK: [u8; 4] = [0x5a, 0xa5, 0x0f, 0xf0];
+
+struct Welch {
+    n: f64,
+    mean: f64,
+    m2: f64,
+}
+
+impl Welch {
+    fn new(samples: &[f64]) -> Self {
+        let mut welch = Self { n: 0.0, mean: 0.0, m2: 0.0 };
+        for &sample in samples {
+            welch.n += 1.0;
+            let delta = sample - welch.mean;
+            welch.mean += delta / welch.n;
+            welch.m2 += delta * (sample - welch.mean);
+        }
+        welch
+    }
+
+    fn variance(&self) -> f64 {
+        self.m2 / (self.n - 1.0)
+    }
+
+    fn t(&self, other: &Welch) -> f64 {
+        (self.mean - other.mean) / (self.variance() / self.n + other.variance() / other.n).sqrt()
+    }
+}
+
+fn crop(samples: &mut Vec<f64>, threshold: f64) {
+    samples.retain(|&sample| sample <= threshold);
+}
+
+fn percentile(samples: &[f64], percentile: f64) -> f64 {
+    let mut sorted = samples.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    sorted[((sorted.len() - 1) as f64 * percentile) as usize]
+}
+
+// Every input is generated before timing starts, and the fixed secret is cloned as many times as there are random
+// ones so that both classes have the same memory footprint.
+fn t_statistic<T: Clone>(fixed: T, random: impl Fn() -> T, operation: impl Fn(&T)) -> f64 {
+    let mut rng = rand::thread_rng();
+    let mut inputs: Vec<(bool, T)> = (0..MEASUREMENTS).flat_map(|_| [(true, fixed.clone()), (false, random())]).collect();
+    inputs.shuffle(&mut rng);
+
+    let mut fixed_timings = Vec::with_capacity(MEASUREMENTS);
+    let mut random_timings = Vec::with_capacity(MEASUREMENTS);
+    for (is_fixed, input) in &inputs {
+        let start = Instant::now();
+        operation(black_box(input));
+        let elapsed = start.elapsed().as_nanos() as f64;
+        if *is_fixed { fixed_timings.push(elapsed) } else { random_timings.push(elapsed) }
+    }
+
+    let threshold = percentile(&[fixed_timings.as_slice(), random_timings.as_slice()].concat(), 0.9);
+    crop(&mut fixed_timings, threshold);
+    crop(&mut random_timings, threshold);
+    Welch::new(&fixed_timings).t(&Welch::new(&random_timings))
+}
+
+#[test]
+#[ignore = "timing-sensitive, run on an idle machine with --ignored"]
+fn test_timing_harness_detects_leaks() {
+    // A deliberately leaky bit permutation that also reads a large table at addresses derived from the permutation:
+    // the fixed one keeps hitting the same cache lines while random ones miss them, and the harness must notice.
+    let table = vec![1u8; TABLE_LENGTH];
+    let t = t_statistic(Permutation::<32>::random(&mut rand::thread_rng()), || Permutation::random(&mut rand::thread_rng()), |permutation| {
+        black_box(permutation.apply_bits(black_box(&BLOCK)));
+        let mut index = permutation.as_array().iter().fold(0usize, |index, &element| index.wrapping_mul(33).wrapping_add(element));
+        for _ in 0..LOOKUPS {
+            index = index.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            black_box(table[index % TABLE_LENGTH]);
+        }
+    });
+    assert!(t.abs() > MAX_T, "the timing harness misses secret-indexed table lookups: t = {}", t);
+}
+
+#[test]
+#[ignore = "timing-sensitive, run on an idle machine with --ignored"]
+fn test_permutation_timing_is_key_independent() {
+    let t = t_statistic(Permutation::<32>::random(&mut rand::thread_rng()), || Permutation::random(&mut rand::thread_rng()), |permutation| {
+        black_box(permutation.apply_bits(black_box(&BLOCK)));
+    });
+    assert!(t.abs() < MAX_T, "bit permutation timing depends on the key: t = {}", t);
+
+    let blocks = [BLOCK; 9];
+    let t = t_statistic(Permutation::<9>::random(&mut rand::thread_rng()), || Permutation::random(&mut rand::thread_rng()), |permutation| {
+        black_box(permutation.apply_blocks(black_box(&blocks)));
+    });
+    assert!(t.abs() < MAX_T, "block permutation timing depends on the key: t = {}", t);
+}
+
+#[test]
+#[ignore = "timing-sensitive, run on an idle machine with --ignored"]
+fn test_re_encryption_timing_is_key_independent() {
+    let message = b"les sanglots longs des violons !";
+    let source_key = Key::generate();
+    let ciphertext = encrypt(message, &source_key);
+    let t = t_statistic(
+        ReEncryptionKey::generate(&source_key, &Key::generate()).unwrap(),
+        || ReEncryptionKey::generate(&source_key, &Key::generate()).unwrap(),
+        |re_encryption_key| {
+            black_box(re_encrypt(black_box(&ciphertext), re_encryption_key));
+        },
+    );
+    assert!(t.abs() < MAX_T, "re-encryption timing depends on the key: t = {}", t);
+}
+
+#[test]
+#[ignore = "timing-sensitive, run on an idle machine with --ignored"]
+fn test_re_encryption_key_generation_timing_is_key_independent() {
+    let source_key = Key::generate();
+    let t = t_statistic(Key::generate(), Key::generate, |target_key| {
+        black_box(ReEncryptionKey::generate(black_box(&source_key), target_key).unwrap());
+    });
+    assert!(t.abs() < MAX_T, "re-encryption key generation timing depends on the key: t = {}", t);
+}
@@ -0,0 +1,28 @@
+#![cfg(all(feature = "wasm", target_arch = "wasm32"))]
+
+use symmetric_pre::wasm::{decrypt, encrypt, re_encrypt, WasmKey, WasmReEncryptionKey};
+use wasm_bindgen_test::wasm_bindgen_test;
+
+#[wasm_bindgen_test]
+fn test_wasm_re_encryption() {
+    let message = b"les sanglots longs des violons !";
+    let key1 = WasmKey::generate();
+    let key2 = WasmKey::from_bytes(&WasmKey::generate().to_bytes()).unwrap();
+    let encrypted = encrypt(message, &key1).unwrap();
+    assert_eq!(decrypt(&encrypted, &key1).unwrap(), message);
+
+    let re_encryption_key = WasmReEncryptionKey::from_bytes(&WasmReEncryptionKey::generate(&key1, &key2).unwrap().to_bytes()).unwrap();
+    let re_encrypted = re_encrypt(&encrypted, &re_encryption_key).unwrap();
+    assert_eq!(decrypt(&re_encrypted, &key2).unwrap(), message);
+}
+
+#[wasm_bindgen_test]
+fn test_wasm_invalid_lengths() {
+    let key = WasmKey::generate();
+    assert!(encrypt(b"too short", &key).is_err());
+    assert!(decrypt(&[0u8; 3], &key).is_err());
+    assert!(WasmKey::from_bytes(b"not a key").is_err());
+    let mut not_a_bijection = key.to_bytes();
+    not_a_bijection[7] = not_a_bijection[6];
+    assert!(WasmKey::from_bytes(&not_a_bijection).is_err());
+}